@@ -1,3 +1,5 @@
+// Variant names match the GraphQL enum values.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
 pub enum State {
     OPEN,
@@ -12,4 +14,43 @@ impl std::fmt::Display for State {
         };
         f.write_str(s)
     }
-}
\ No newline at end of file
+}
+
+/// Format a date so that it can be used in search queries, eg `updated:>={date}`.
+pub fn format_search_date(date: time::OffsetDateTime) -> String {
+    let format = time::format_description::well_known::Iso8601::DEFAULT;
+    date.format(&format).expect("valid iso8601")
+}
+
+/// A search qualifier restricting results to things updated since the given date,
+/// or an empty string if no date is given.
+pub fn updated_since_qualifier(updated_since: Option<time::OffsetDateTime>) -> String {
+    match updated_since {
+        Some(date) => format!("updated:>={}", format_search_date(date)),
+        None => String::new()
+    }
+}
+
+/// Search qualifiers restricting results to any of the given orgs.
+pub fn orgs_qualifier(orgs: &[String]) -> String {
+    orgs.iter().map(|org| format!("org:{org}")).collect::<Vec<_>>().join(" ")
+}
+
+/// Search qualifiers restricting results to any of the given repos, given as `owner/name`.
+pub fn repos_qualifier(repos: &[String]) -> String {
+    repos.iter().map(|repo| format!("repo:{repo}")).collect::<Vec<_>>().join(" ")
+}
+
+/// The value of a field on a project item.
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum FieldValue {
+    Text(String),
+    Number(f64),
+    /// A date in the form `YYYY-MM-DD`.
+    Date(String),
+    /// The ID of a single select option.
+    SingleSelect(String),
+    /// The ID of an iteration.
+    Iteration(String),
+}
@@ -123,7 +123,7 @@ pub enum ApiError {
     RequestError(#[from] reqwest::Error),
     #[error("{0} response: {1}")]
     BadResponse(u16, String),
-    #[error("Errors with query: {0:?}")]
+    #[error("Errors with query: {}", join_query_errors(.0))]
     QueryErrors(Vec<QueryError>),
     #[error("{0}")]
     DecodeError(#[from] anyhow::Error),
@@ -135,6 +135,19 @@ pub struct QueryError {
     pub message: String
 }
 
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{} (at {})", self.message, path.join(".")),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+fn join_query_errors(errors: &[QueryError]) -> String {
+    errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; ")
+}
+
 #[macro_export]
 macro_rules! variables {
     ($($key:literal : $val:expr), *) => {{
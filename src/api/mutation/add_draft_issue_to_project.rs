@@ -0,0 +1,55 @@
+use crate::api::Api;
+use crate::variables;
+
+const MUTATION: &str = r#"
+    mutation AddDraftIssueToProject($project_id:ID!, $title:String!, $body:String!) {
+        res: addProjectV2DraftIssue(input: {projectId:$project_id, title:$title, body:$body}) {
+            item: projectItem {
+                id
+                content {
+                    ... on DraftIssue {
+                        created_at: createdAt
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+#[derive(Debug)]
+pub struct DraftIssueItem {
+    /// The "item ID" which represents the project card.
+    pub item_id: String,
+    /// When GitHub created the draft issue.
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Add a draft issue to a project.
+pub async fn run(api: &Api, project_id: &str, title: &str, body: &str) -> Result<DraftIssueItem, anyhow::Error> {
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        res: QueryAddDraftIssue
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryAddDraftIssue {
+        item: QueryItem
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryItem {
+        id: String,
+        content: QueryDraftIssue
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryDraftIssue {
+        #[serde(with = "time::serde::iso8601")]
+        created_at: time::OffsetDateTime
+    }
+
+    let res: QueryResult = api.query(MUTATION, variables!{
+        "project_id": project_id,
+        "title": title,
+        "body": body
+    }).await?;
+
+    Ok(DraftIssueItem { item_id: res.res.item.id, created_at: res.res.item.content.created_at })
+}
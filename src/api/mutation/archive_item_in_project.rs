@@ -0,0 +1,23 @@
+use crate::api::Api;
+use crate::variables;
+
+const MUTATION: &str = r#"
+    mutation ArchiveItemInProject($project_id:ID!, $item_id:ID!) {
+        archiveProjectV2Item(input:{projectId:$project_id, itemId:$item_id}) {
+            clientMutationId
+        }
+    }
+"#;
+
+/// Archive an item, hiding it from the board but keeping it (and its field values) in the project.
+pub async fn run(api: &Api, project_id: &str, item_id: &str) -> Result<(), anyhow::Error> {
+    #[derive(serde::Deserialize)]
+    struct QueryResult {}
+
+    let _res: QueryResult = api.query(MUTATION, variables!{
+        "project_id": project_id,
+        "item_id": item_id
+    }).await?;
+
+    Ok(())
+}
@@ -3,4 +3,8 @@ pub mod update_issue;
 pub mod add_item_to_project;
 pub mod update_item_field_in_project;
 pub mod clear_item_field_in_project;
-pub mod remove_item_from_project;
\ No newline at end of file
+pub mod remove_item_from_project;
+pub mod add_draft_issue_to_project;
+pub mod archive_item_in_project;
+pub mod unarchive_item_in_project;
+pub mod update_item_position_in_project;
@@ -0,0 +1,23 @@
+use crate::api::Api;
+use crate::variables;
+
+const MUTATION: &str = r#"
+    mutation UnarchiveItemInProject($project_id:ID!, $item_id:ID!) {
+        unarchiveProjectV2Item(input:{projectId:$project_id, itemId:$item_id}) {
+            clientMutationId
+        }
+    }
+"#;
+
+/// Unarchive an item, putting it back on the board.
+pub async fn run(api: &Api, project_id: &str, item_id: &str) -> Result<(), anyhow::Error> {
+    #[derive(serde::Deserialize)]
+    struct QueryResult {}
+
+    let _res: QueryResult = api.query(MUTATION, variables!{
+        "project_id": project_id,
+        "item_id": item_id
+    }).await?;
+
+    Ok(())
+}
@@ -1,25 +1,33 @@
-use crate::api::Api;
+use crate::api::{ Api, common::FieldValue };
 use crate::variables;
 
 const MUTATION: &str = r#"
-    mutation UpdateItemFieldInProject($item_id:ID!, $project_id:ID!, $field_id:ID!, $field_value:String!) {
+    mutation UpdateItemFieldInProject($item_id:ID!, $project_id:ID!, $field_id:ID!, $field_value:ProjectV2FieldValue!) {
         updateProjectV2ItemFieldValue(input:{
             itemId:$item_id,
             projectId:$project_id,
             fieldId:$field_id,
-            # It just happens that we only care about updating single select options:
-            value: { singleSelectOptionId:$field_value }
+            value:$field_value
         }) {
             clientMutationId
         }
     }
 "#;
 
-/// Returns an "item ID" which represents the project card.
-pub async fn run(api: &Api, project_id: &str, item_id: &str, field_id: &str, field_value: &str) -> Result<(), anyhow::Error> {
+/// Set a field on a project item to the given value.
+pub async fn run(api: &Api, project_id: &str, item_id: &str, field_id: &str, field_value: &FieldValue) -> Result<(), anyhow::Error> {
     #[derive(serde::Deserialize)]
     struct QueryResult {}
 
+    // The input is an object with a single key saying which type of value is being set.
+    let field_value = match field_value {
+        FieldValue::Text(text) => serde_json::json!({ "text": text }),
+        FieldValue::Number(number) => serde_json::json!({ "number": number }),
+        FieldValue::Date(date) => serde_json::json!({ "date": date }),
+        FieldValue::SingleSelect(option_id) => serde_json::json!({ "singleSelectOptionId": option_id }),
+        FieldValue::Iteration(iteration_id) => serde_json::json!({ "iterationId": iteration_id }),
+    };
+
     let _res: QueryResult = api.query(MUTATION, variables!{
         "project_id": project_id,
         "item_id": item_id,
@@ -28,4 +36,4 @@ pub async fn run(api: &Api, project_id: &str, item_id: &str, field_id: &str, fie
     }).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
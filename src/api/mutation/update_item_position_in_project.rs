@@ -0,0 +1,24 @@
+use crate::api::Api;
+use crate::variables;
+
+const MUTATION: &str = r#"
+    mutation UpdateItemPositionInProject($project_id:ID!, $item_id:ID!, $after_id:ID) {
+        updateProjectV2ItemPosition(input:{projectId:$project_id, itemId:$item_id, afterId:$after_id}) {
+            clientMutationId
+        }
+    }
+"#;
+
+/// Move an item so that it comes straight after another item, or to the top if no item is given.
+pub async fn run(api: &Api, project_id: &str, item_id: &str, after_id: Option<&str>) -> Result<(), anyhow::Error> {
+    #[derive(serde::Deserialize)]
+    struct QueryResult {}
+
+    let _res: QueryResult = api.query(MUTATION, variables!{
+        "project_id": project_id,
+        "item_id": item_id,
+        "after_id": after_id
+    }).await?;
+
+    Ok(())
+}
@@ -1,28 +1,56 @@
-use crate::api::Api;
+use crate::api::{ Api, common::{ format_search_date, orgs_qualifier, repos_qualifier, updated_since_qualifier } };
 use crate::variables;
 
-const CLOSED_ISSUES_QUERY: &str = r#"
-query ClosedIssues($query:String!, $cursor:String) {
-    search(after:$cursor, last: 100, query:$query, type: ISSUE) {
-        nodes {
-            ... on Issue {
-                id
+const CLOSED_THINGS_QUERY: &str = r#"
+    query ClosedThings($query:String!, $cursor:String) {
+        search(after:$cursor, first: 100, query:$query, type: ISSUE) {
+            nodes {
+                ... on Issue {
+                    id
+                    closed_at: closedAt
+                    repository {
+                        name_with_owner: nameWithOwner
+                    }
+                }
+                ... on PullRequest {
+                    id
+                    closed_at: closedAt
+                    repository {
+                        name_with_owner: nameWithOwner
+                    }
+                }
+            }
+            page: pageInfo {
+                has_next_page: hasNextPage
+                cursor: endCursor
             }
-        }
-        page: pageInfo {
-            has_next_page: hasNextPage
-            cursor: endCursor
         }
     }
-}
 "#;
 
-const MERGED_PRS_QUERY: &str = r#"
-    query MergedPrs($query:String!, $cursor:String) {
-        search(after:$cursor, last: 100, query:$query, type: ISSUE) {
+const COAUTHORED_PRS_QUERY: &str = r#"
+    query CoauthoredPrs($query:String!, $cursor:String) {
+        search(after:$cursor, first: 50, query:$query, type: ISSUE) {
             nodes {
                 ... on PullRequest {
                     id
+                    closed_at: closedAt
+                    repository {
+                        name_with_owner: nameWithOwner
+                    }
+                    commits(first: 100) {
+                        nodes {
+                            commit {
+                                authors(first: 10) {
+                                    nodes {
+                                        user {
+                                            login
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
                 }
             }
             page: pageInfo {
@@ -33,7 +61,58 @@ const MERGED_PRS_QUERY: &str = r#"
     }
 "#;
 
-pub async fn run(api: &Api, org: &str, user_names: &[String]) -> Result<ClosedThings, anyhow::Error> {
+/// A closed issue or merged PR.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ClosedThing {
+    pub id: String,
+    #[serde(with = "time::serde::iso8601")]
+    pub closed_at: time::OffsetDateTime,
+    #[serde(deserialize_with = "deserialize_repository")]
+    pub repository: String,
+}
+
+/// Issues in any of the given orgs assigned to any of the given users that were closed since the given time.
+pub async fn closed_issues(api: &Api, orgs: &[String], user_names: &[String], closed_since: time::OffsetDateTime, updated_since: Option<time::OffsetDateTime>) -> Result<Vec<ClosedThing>, anyhow::Error> {
+    let mut assignees_query = String::new();
+    for name in user_names {
+        assignees_query.push_str(" assignee:");
+        assignees_query.push_str(name);
+    }
+
+    let closed_since = format_search_date(closed_since);
+    let updated_query = updated_since_qualifier(updated_since);
+    let orgs_query = orgs_qualifier(orgs);
+    let closed_issues_query = format!("type:issue state:closed closed:>={closed_since} {updated_query} {orgs_query} {assignees_query}");
+
+    do_search(api, CLOSED_THINGS_QUERY, &closed_issues_query).await
+}
+
+/// PRs authored (and optionally, reviewed) by any of the given users that were merged since the given time.
+pub async fn merged_prs(api: &Api, orgs: &[String], user_names: &[String], include_reviewed: bool, closed_since: time::OffsetDateTime, updated_since: Option<time::OffsetDateTime>) -> Result<Vec<ClosedThing>, anyhow::Error> {
+    let closed_since = format_search_date(closed_since);
+    let updated_query = updated_since_qualifier(updated_since);
+    let orgs_query = orgs_qualifier(orgs);
+
+    let mut merged_prs = Vec::new();
+    let qualifiers: &[&str] = if include_reviewed { &["author", "reviewed-by"] } else { &["author"] };
+    for qualifier in qualifiers {
+        let mut users_query = String::new();
+        for name in user_names {
+            users_query.push_str(&format!(" {qualifier}:{name}"));
+        }
+        let merged_prs_query = format!("type:pr is:merged state:closed closed:>={closed_since} {updated_query} {orgs_query} {users_query}");
+        merged_prs.extend(do_search(api, CLOSED_THINGS_QUERY, &merged_prs_query).await?);
+    }
+
+    // PRs that were both authored and reviewed by team members will be found twice.
+    let mut seen = std::collections::HashSet::new();
+    merged_prs.retain(|pr| seen.insert(pr.id.clone()));
+    Ok(merged_prs)
+}
+
+/// PRs in the given repos (as `owner/name`) that were merged since the given time, and which have commits (co-)authored
+/// by any of the given users. Co-authors can't be searched for, so this looks through every merged PR.
+pub async fn coauthored_merged_prs(api: &Api, user_names: &[String], repos: &[String], closed_since: time::OffsetDateTime, updated_since: Option<time::OffsetDateTime>) -> Result<Vec<ClosedThing>, anyhow::Error> {
     // The shape we want to deserialize to.
     #[derive(serde::Deserialize)]
     struct QueryResult {
@@ -41,73 +120,107 @@ pub async fn run(api: &Api, org: &str, user_names: &[String]) -> Result<ClosedTh
     }
     #[derive(serde::Deserialize)]
     struct QuerySearch {
-        nodes: Vec<QueryItem>,
+        nodes: Vec<QueryPr>,
         page: PageInfo
     }
     #[derive(serde::Deserialize)]
-    struct PageInfo {
-        has_next_page: bool,
-        cursor: Option<String>
+    struct QueryPr {
+        #[serde(flatten)]
+        pr: ClosedThing,
+        commits: QueryNodes<QueryCommit>
     }
     #[derive(serde::Deserialize)]
-    struct QueryItem {
-        id: String
+    struct QueryNodes<T> {
+        nodes: Vec<T>
     }
-
-    let mut assignees_query = String::new();
-    for name in user_names {
-        assignees_query.push_str(" assignee:");
-        assignees_query.push_str(&name);
+    #[derive(serde::Deserialize)]
+    struct QueryCommit {
+        commit: QueryCommitAuthors
     }
-
-    let mut authors_query = String::new();
-    for name in user_names {
-        authors_query.push_str(" author:");
-        authors_query.push_str(&name);
+    #[derive(serde::Deserialize)]
+    struct QueryCommitAuthors {
+        authors: QueryNodes<QueryAuthor>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryAuthor {
+        // Commit authors don't always map to a GitHub user.
+        user: Option<QueryUser>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryUser {
+        login: String
     }
 
-    // We'll get all things closed in the last 28 days
-    let a_month_ago = {
-        let now = time::OffsetDateTime::now_utc();
-        let format = time::format_description::well_known::Iso8601::DEFAULT;
-        let a_month_ago: time::OffsetDateTime = now - (time::Duration::DAY * 28);
-        a_month_ago.format(&format).expect("valid iso8601")
-    };
-
-    let closed_issues_query = format!("type:issue state:closed closed:>={a_month_ago} org:{org} {assignees_query}");
-    let merged_prs_query = format!("type:pr is:merged state:closed closed:>={a_month_ago} org:{org} {authors_query}");
-
-    async fn do_search(api: &Api, ql: &str, query: &str) -> Result<Vec<String>, anyhow::Error> {
-        let mut cursor = None;
-        let mut ids = Vec::new();
-        loop {
-            let res: QueryResult = api.query(ql, variables!(
-                "query": query,
-                "cursor": cursor
-            )).await?;
-
-            let new_ids = res.search.nodes.into_iter().map(|n| n.id);
-            ids.extend(new_ids);
-
-            cursor = res.search.page.cursor;
-            if !res.search.page.has_next_page || cursor.is_none() {
-                break;
-            }
+    let repos_query = repos_qualifier(repos);
+    let closed_since = format_search_date(closed_since);
+    let updated_query = updated_since_qualifier(updated_since);
+    let query = format!("type:pr is:merged state:closed closed:>={closed_since} {updated_query} {repos_query}");
+
+    let mut cursor = None;
+    let mut prs = Vec::new();
+    loop {
+        let res: QueryResult = api.query(COAUTHORED_PRS_QUERY, variables!(
+            "query": &query,
+            "cursor": cursor
+        )).await?;
+
+        let coauthored = res.search.nodes.into_iter().filter(|pr| {
+            pr.commits.nodes.iter()
+                .flat_map(|c| &c.commit.authors.nodes)
+                .filter_map(|a| a.user.as_ref())
+                .any(|user| user_names.iter().any(|name| name.eq_ignore_ascii_case(&user.login)))
+        });
+        prs.extend(coauthored.map(|pr| pr.pr));
+
+        cursor = res.search.page.cursor;
+        if !res.search.page.has_next_page || cursor.is_none() {
+            break;
         }
-        Ok(ids)
     }
+    Ok(prs)
+}
 
-    let closed_issues = do_search(api, CLOSED_ISSUES_QUERY, &closed_issues_query).await?;
-    let merged_prs = do_search(api, MERGED_PRS_QUERY, &merged_prs_query).await?;
+// The shape we want to deserialize to.
+#[derive(serde::Deserialize)]
+struct QueryResult {
+    search: QuerySearch
+}
+#[derive(serde::Deserialize)]
+struct QuerySearch {
+    nodes: Vec<ClosedThing>,
+    page: PageInfo
+}
+#[derive(serde::Deserialize)]
+struct PageInfo {
+    has_next_page: bool,
+    cursor: Option<String>
+}
 
+async fn do_search(api: &Api, ql: &str, query: &str) -> Result<Vec<ClosedThing>, anyhow::Error> {
+    let mut cursor = None;
+    let mut things = Vec::new();
+    loop {
+        let res: QueryResult = api.query(ql, variables!(
+            "query": query,
+            "cursor": cursor
+        )).await?;
 
-    Ok(ClosedThings {
-        closed_issues,
-        merged_prs
-    })
+        things.extend(res.search.nodes);
+
+        cursor = res.search.page.cursor;
+        if !res.search.page.has_next_page || cursor.is_none() {
+            break;
+        }
+    }
+    Ok(things)
 }
 
-pub struct ClosedThings {
-    pub closed_issues: Vec<String>,
-    pub merged_prs: Vec<String>
-}
\ No newline at end of file
+// Repositories come back as `{ name_with_owner: "owner/name" }`.
+fn deserialize_repository<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(serde::Deserialize)]
+    struct QueryRepository {
+        name_with_owner: String
+    }
+    let repo: QueryRepository = serde::Deserialize::deserialize(deserializer)?;
+    Ok(repo.name_with_owner)
+}
@@ -0,0 +1,165 @@
+use crate::api::{ Api, common::{ repos_qualifier, updated_since_qualifier } };
+use crate::variables;
+
+const COMMUNITY_QUERY: &str = r#"
+    query Community($query:String!, $cursor:String) {
+        search(after:$cursor, first:50, query:$query, type:ISSUE) {
+            nodes {
+                ... on Issue {
+                    id
+                    ...Activity
+                }
+                ... on PullRequest {
+                    id
+                    ...Activity
+                    reviews(last:50) {
+                        nodes {
+                            author {
+                                login
+                            }
+                            created_at: createdAt
+                        }
+                    }
+                }
+            }
+            page: pageInfo {
+                has_next_page: hasNextPage
+                cursor: endCursor
+            }
+        }
+    }
+
+    fragment Activity on Comment {
+        author {
+            login
+        }
+        created_at: createdAt
+        ... on Issue {
+            comments(last:50) {
+                nodes {
+                    author {
+                        login
+                    }
+                    created_at: createdAt
+                }
+            }
+        }
+        ... on PullRequest {
+            comments(last:50) {
+                nodes {
+                    author {
+                        login
+                    }
+                    created_at: createdAt
+                }
+            }
+        }
+    }
+"#;
+
+/// An issue or PR opened by somebody outside of the team.
+#[derive(Debug, Clone)]
+pub struct Contribution {
+    pub id: String,
+    /// When the author (or somebody else outside of the team) started waiting for a response
+    /// from the team, or `None` if a team member has responded to everything so far.
+    pub waiting_since: Option<time::OffsetDateTime>,
+}
+
+/// Find open PRs, and open issues that nobody is assigned to, in the given repos (as `owner/name`) which were opened by
+/// anybody other than the given team members and excluded authors (eg bots).
+pub async fn run(api: &Api, repos: &[String], team_members: &[String], excluded_authors: &[String], updated_since: Option<time::OffsetDateTime>) -> Result<Vec<Contribution>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        search: QuerySearch
+    }
+    #[derive(serde::Deserialize)]
+    struct QuerySearch {
+        nodes: Vec<QueryItem>,
+        page: PageInfo
+    }
+    #[derive(serde::Deserialize)]
+    struct PageInfo {
+        has_next_page: bool,
+        cursor: Option<String>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryItem {
+        id: String,
+        author: Option<QueryLogin>,
+        #[serde(with = "time::serde::iso8601")]
+        created_at: time::OffsetDateTime,
+        comments: QueryNodes<QueryActivity>,
+        // Only PRs have reviews.
+        #[serde(default)]
+        reviews: Option<QueryNodes<QueryActivity>>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryNodes<T> {
+        nodes: Vec<T>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryActivity {
+        author: Option<QueryLogin>,
+        #[serde(with = "time::serde::iso8601")]
+        created_at: time::OffsetDateTime
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryLogin {
+        login: String
+    }
+
+    let is_one_of = |login: &str, names: &[String]| names.iter().any(|name| name.eq_ignore_ascii_case(login));
+
+    let repos_query = repos_qualifier(repos);
+    let updated_query = updated_since_qualifier(updated_since);
+    // Authors can't be excluded in the search itself without hitting query length limits, so we filter them out below.
+    let queries = [
+        format!("is:pr is:open {updated_query} {repos_query}"),
+        format!("is:issue is:open no:assignee {updated_query} {repos_query}"),
+    ];
+
+    let mut contributions = Vec::new();
+    for query in &queries {
+        let mut cursor = None;
+        loop {
+            let res: QueryResult = api.query(COMMUNITY_QUERY, variables!(
+                "query": query,
+                "cursor": cursor
+            )).await?;
+
+            for item in res.search.nodes {
+                // Deleted users show up as "ghost", so they count as being outside of the team.
+                let author = item.author.as_ref().map_or("ghost", |a| &*a.login);
+                if is_one_of(author, team_members) || is_one_of(author, excluded_authors) {
+                    continue
+                }
+
+                let mut activity: Vec<(bool, time::OffsetDateTime)> = item.comments.nodes
+                    .iter()
+                    .chain(item.reviews.iter().flat_map(|r| &r.nodes))
+                    .map(|a| {
+                        let is_team = a.author.as_ref().is_some_and(|a| is_one_of(&a.login, team_members));
+                        (is_team, a.created_at)
+                    })
+                    .collect();
+                activity.sort_by_key(|(_, at)| *at);
+
+                // We're waiting to respond to the first thing said after the last team response.
+                let waiting_since = match activity.iter().rposition(|(is_team, _)| *is_team) {
+                    Some(idx) => activity.get(idx + 1).map(|(_, at)| *at),
+                    None => Some(item.created_at),
+                };
+
+                contributions.push(Contribution { id: item.id, waiting_since });
+            }
+
+            cursor = res.search.page.cursor;
+            if !res.search.page.has_next_page || cursor.is_none() {
+                break;
+            }
+        }
+    }
+    Ok(contributions)
+}
@@ -0,0 +1,140 @@
+use crate::api::Api;
+use crate::variables;
+use std::collections::HashMap;
+
+const CONTENT_SUMMARIES_QUERY: &str = r#"
+    query ContentSummaries($ids:[ID!]!) {
+        nodes(ids:$ids) {
+            __typename
+            ... on Issue {
+                id
+                title
+                url
+                updated_at: updatedAt
+                author {
+                    login
+                }
+                repository {
+                    name_with_owner: nameWithOwner
+                }
+                labels(first:50) {
+                    nodes {
+                        name
+                    }
+                }
+            }
+            ... on PullRequest {
+                id
+                title
+                url
+                updated_at: updatedAt
+                author {
+                    login
+                }
+                repository {
+                    name_with_owner: nameWithOwner
+                }
+                labels(first:50) {
+                    nodes {
+                        name
+                    }
+                }
+                closing_issues: closingIssuesReferences(first:10) {
+                    nodes {
+                        id
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+// GitHub won't look up more than this many nodes at once.
+const IDS_PER_QUERY: usize = 100;
+
+/// A summary of an issue or PR.
+#[derive(Debug, Clone)]
+pub struct ContentSummary {
+    pub title: String,
+    pub url: String,
+    /// In the form `owner/name`.
+    pub repository: String,
+    /// The login of the author, if they still exist.
+    pub author: Option<String>,
+    pub labels: Vec<String>,
+    pub updated_at: time::OffsetDateTime,
+    /// For PRs, the IDs of the issues that it will close when merged.
+    pub closing_issue_ids: Vec<String>,
+}
+
+/// Look up a summary of each of the issues and PRs with the given IDs, keyed by ID.
+pub async fn run(api: &Api, ids: &[&str]) -> Result<HashMap<String, ContentSummary>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        nodes: Vec<Option<QueryNode>>
+    }
+    #[derive(serde::Deserialize)]
+    #[serde(tag = "__typename")]
+    enum QueryNode {
+        Issue(QueryContent),
+        PullRequest(QueryContent),
+        #[serde(other)]
+        Other
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryContent {
+        id: String,
+        title: String,
+        url: String,
+        #[serde(with = "time::serde::iso8601")]
+        updated_at: time::OffsetDateTime,
+        repository: QueryRepository,
+        author: Option<QueryLogin>,
+        labels: QueryNodes<QueryLabel>,
+        // Only PRs close issues.
+        #[serde(default)]
+        closing_issues: Option<QueryNodes<QueryId>>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRepository {
+        name_with_owner: String
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryLogin {
+        login: String
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryNodes<T> {
+        nodes: Vec<T>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryLabel {
+        name: String
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryId {
+        id: String
+    }
+
+    let mut summaries = HashMap::new();
+    for ids in ids.chunks(IDS_PER_QUERY) {
+        let res: QueryResult = api.query(CONTENT_SUMMARIES_QUERY, variables!(
+            "ids": ids
+        )).await?;
+
+        for node in res.nodes.into_iter().flatten() {
+            let (QueryNode::Issue(content) | QueryNode::PullRequest(content)) = node else { continue };
+            summaries.insert(content.id, ContentSummary {
+                title: content.title,
+                url: content.url,
+                repository: content.repository.name_with_owner,
+                author: content.author.map(|a| a.login),
+                labels: content.labels.nodes.into_iter().map(|l| l.name).collect(),
+                updated_at: content.updated_at,
+                closing_issue_ids: content.closing_issues.into_iter().flat_map(|c| c.nodes).map(|i| i.id).collect(),
+            });
+        }
+    }
+    Ok(summaries)
+}
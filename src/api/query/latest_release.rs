@@ -0,0 +1,42 @@
+use crate::api::Api;
+use crate::variables;
+
+const LATEST_RELEASE_QUERY: &str = r#"
+    query LatestRelease($owner:String!, $name:String!) {
+        repository(owner:$owner, name:$name) {
+            latest_release: latestRelease {
+                created_at: createdAt
+            }
+        }
+    }
+"#;
+
+/// When the latest release of the given repo (in the form `owner/name`) was created,
+/// or `None` if it has no releases.
+pub async fn run(api: &Api, repo: &str) -> Result<Option<time::OffsetDateTime>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        repository: QueryRepository
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRepository {
+        latest_release: Option<QueryRelease>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRelease {
+        #[serde(with = "time::serde::iso8601")]
+        created_at: time::OffsetDateTime
+    }
+
+    let Some((owner, name)) = repo.split_once('/') else {
+        anyhow::bail!("Expected repo '{repo}' to be in the form owner/name")
+    };
+
+    let res: QueryResult = api.query(LATEST_RELEASE_QUERY, variables!(
+        "owner": owner,
+        "name": name
+    )).await?;
+
+    Ok(res.repository.latest_release.map(|r| r.created_at))
+}
@@ -3,14 +3,16 @@ use crate::variables;
 use std::collections::HashMap;
 
 const MILESTONES_QUERY: &str = r#"
-    query MilestonesQuery($org: String!, $repo: String!) {
-        repository(owner: $org, name: $repo) {
+    query MilestonesQuery($owner: String!, $repo: String!) {
+        repository(owner: $owner, name: $repo) {
             milestones(first:100, orderBy:{ field:UPDATED_AT, direction:DESC}) {
                 nodes {
+                    id
                     number
                     title
                     description
                     dueOn
+                    updatedAt
                     state
                 }
             }
@@ -20,11 +22,14 @@ const MILESTONES_QUERY: &str = r#"
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Milestone {
+    pub id: String,
     pub number: usize,
     pub title: String,
     pub state: crate::api::common::State,
     #[serde(rename = "dueOn")]
     pub due_on: Option<DueDate>,
+    #[serde(rename = "updatedAt", with = "time::serde::iso8601")]
+    pub updated_at: time::OffsetDateTime,
     pub description: String,
 }
 
@@ -35,7 +40,9 @@ pub struct DueDate {
     pub time: time::OffsetDateTime
 }
 
-pub async fn run(api: &Api, org: &str, repo_names: &[String]) -> Result<HashMap<String, Vec<Milestone>>, anyhow::Error> {
+/// Fetch the 100 most recently updated milestones in each of the given repos (as `owner/name`). If
+/// `updated_since` is given, only milestones updated since then are returned.
+pub async fn run(api: &Api, repo_names: &[String], updated_since: Option<time::OffsetDateTime>) -> Result<HashMap<String, Vec<Milestone>>, anyhow::Error> {
     // The shape we want to deserialize to.
     #[derive(serde::Deserialize)]
     struct QueryResult {
@@ -52,12 +59,18 @@ pub async fn run(api: &Api, org: &str, repo_names: &[String]) -> Result<HashMap<
 
     let mut milestones_by_repo = HashMap::new();
     for repo in repo_names {
+        let Some((owner, name)) = repo.split_once('/') else {
+            anyhow::bail!("Expected a repo in the form 'owner/name' but got '{repo}'")
+        };
         let res: QueryResult = api.query(MILESTONES_QUERY, variables!(
-            "org": org,
-            "repo": repo
+            "owner": owner,
+            "repo": name
         )).await?;
 
-        let milestones = res.repository.milestones.nodes;
+        let milestones = res.repository.milestones.nodes
+            .into_iter()
+            .filter(|m| updated_since.is_none_or(|since| m.updated_at >= since))
+            .collect();
         milestones_by_repo.insert(repo.to_string(), milestones);
     }
 
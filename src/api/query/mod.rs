@@ -5,3 +5,9 @@ pub mod open_assigned_issues;
 pub mod closed_things;
 pub mod team_prs;
 pub mod project_items;
+pub mod project_leases;
+pub mod search;
+pub mod latest_release;
+pub mod community;
+pub mod content_summaries;
+pub mod team;
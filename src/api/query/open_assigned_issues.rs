@@ -1,4 +1,4 @@
-use crate::api::Api;
+use crate::api::{ Api, common::{ orgs_qualifier, updated_since_qualifier }, query::project_items::ContentState };
 use crate::variables;
 
 const ISSUES_QUERY: &str = r#"
@@ -6,15 +6,63 @@ const ISSUES_QUERY: &str = r#"
         search(last:100, query:$query, type:ISSUE) {
             nodes {
                 ... on Issue {
-                    id,
-                    repository{ name }
+                    ...AssignedIssueDetails
                 }
             }
         }
     }
 "#;
 
-pub async fn run(api: &Api, org: &str, user_names: &[String], local_issue_repo_name: &str) -> Result<Vec<String>, anyhow::Error> {
+// Opening, closing or marking a PR as ready for review doesn't count as updating the issues
+// that it will close, so when looking for recently updated issues we look for the issues
+// that recently updated PRs will close too.
+const LINKED_PRS_QUERY: &str = r#"
+    query RecentlyUpdatedLinkedPrs($query:String!) {
+        search(last:100, query:$query, type:ISSUE) {
+            nodes {
+                ... on PullRequest {
+                    closing_issues: closingIssuesReferences(first:10) {
+                        nodes {
+                            ...AssignedIssueDetails
+                        }
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+// The details we want about every issue.
+const ISSUE_DETAILS: &str = r#"
+    fragment AssignedIssueDetails on Issue {
+        id
+        state
+        repository {
+            name_with_owner: nameWithOwner
+        }
+        assignees(first:20) {
+            nodes {
+                login
+            }
+        }
+        linked_prs: closedByPullRequestsReferences(first:10, includeClosedPrs:false) {
+            nodes {
+                state
+                draft: isDraft
+            }
+        }
+    }
+"#;
+
+/// An open issue assigned to somebody.
+#[derive(Debug, Clone)]
+pub struct AssignedIssue {
+    pub id: String,
+    /// Is there an open, non-draft PR which will close this issue when merged?
+    pub has_pr_in_review: bool,
+}
+
+pub async fn run(api: &Api, orgs: &[String], user_names: &[String], local_issue_repo: &str, updated_since: Option<time::OffsetDateTime>) -> Result<Vec<AssignedIssue>, anyhow::Error> {
     // The shape we want to deserialize to.
     #[derive(serde::Deserialize)]
     struct QueryResult {
@@ -25,13 +73,40 @@ pub async fn run(api: &Api, org: &str, user_names: &[String], local_issue_repo_n
         nodes: Vec<QueryIssue>
     }
     #[derive(serde::Deserialize)]
+    struct LinkedPrsQueryResult {
+        search: QueryPrSearch
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryPrSearch {
+        nodes: Vec<QueryPr>
+    }
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum QueryPr {
+        PullRequest { closing_issues: QueryNodes<QueryIssue> },
+        Unknown {}
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryNodes<T> {
+        nodes: Vec<T>
+    }
+    #[derive(serde::Deserialize)]
     struct QueryRepository {
-        name: String,
+        name_with_owner: String,
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryLogin {
+        login: String
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryLinkedPr {
+        state: ContentState,
+        draft: bool
     }
     #[derive(serde::Deserialize)]
     #[serde(untagged)]
     enum QueryIssue {
-        Issue { id: String, repository: QueryRepository },
+        Issue { id: String, state: ContentState, repository: QueryRepository, assignees: QueryNodes<QueryLogin>, linked_prs: QueryNodes<QueryLinkedPr> },
         Unknown {}
     }
 
@@ -40,24 +115,46 @@ pub async fn run(api: &Api, org: &str, user_names: &[String], local_issue_repo_n
     let mut user_names_query = String::new();
     for name in user_names {
         user_names_query.push_str(" assignee:");
-        user_names_query.push_str(&name);
+        user_names_query.push_str(name);
     }
-    let query = format!("state:open org:{org} {user_names_query}");
+    let orgs_query = orgs_qualifier(orgs);
+    let updated_query = updated_since_qualifier(updated_since);
+    let query = format!("state:open {orgs_query} {updated_query} {user_names_query}");
 
-    let res: QueryResult = api.query(ISSUES_QUERY, variables!(
-        "org": org,
+    let res: QueryResult = api.query(&format!("{ISSUES_QUERY}{ISSUE_DETAILS}"), variables!(
         "query": query
     )).await?;
+    let mut found = res.search.nodes;
 
-    let issue_ids = res.search.nodes
-        .into_iter()
-        .filter_map(|n| {
-            match n {
-                QueryIssue::Issue { id, repository } if repository.name != local_issue_repo_name => Some(id),
-                _ => None
+    if updated_since.is_some() {
+        let linked_prs_query = format!("is:pr linked:issue {orgs_query} {updated_query}");
+        let res: LinkedPrsQueryResult = api.query(&format!("{LINKED_PRS_QUERY}{ISSUE_DETAILS}"), variables!(
+            "query": linked_prs_query
+        )).await?;
+        for pr in res.search.nodes {
+            if let QueryPr::PullRequest { closing_issues } = pr {
+                found.extend(closing_issues.nodes);
             }
-        })
-        .collect();
+        }
+    }
+
+    let is_one_of = |name: &str, names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+    let mut issues: Vec<AssignedIssue> = Vec::new();
+    for issue in found {
+        // Issues found via PRs haven't been filtered by the search, so check everything here.
+        let QueryIssue::Issue { id, state, repository, assignees, linked_prs } = issue else { continue };
+        let owner = repository.name_with_owner.split('/').next().unwrap_or_default();
+        if state != ContentState::Open
+            || !is_one_of(owner, orgs)
+            || repository.name_with_owner.eq_ignore_ascii_case(local_issue_repo)
+            || !assignees.nodes.iter().any(|a| is_one_of(&a.login, user_names))
+            || issues.iter().any(|i| i.id == id)
+        {
+            continue
+        }
+        let has_pr_in_review = linked_prs.nodes.iter().any(|pr| pr.state == ContentState::Open && !pr.draft);
+        issues.push(AssignedIssue { id, has_pr_in_review });
+    }
 
-    Ok(issue_ids)
+    Ok(issues)
 }
\ No newline at end of file
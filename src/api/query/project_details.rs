@@ -17,6 +17,11 @@ const PROJECTS_QUERY: &str = r#"
                         }
                     }
                 }
+                fields(first:100) {
+                    nodes {
+                        ...ProjectFieldDetails
+                    }
+                }
             }
             roadmap: projectV2(number: $roadmap_project) {
                 id
@@ -56,6 +61,59 @@ const PROJECTS_QUERY: &str = r#"
     }
 "#;
 
+const PROJECT_QUERY: &str = r#"
+    query ProjectQuery($org: String!, $project:Int!) {
+        organization(login: $org) {
+            project: projectV2(number: $project) {
+                id
+                number,
+                status: field(name:"Status") {
+                    ... on ProjectV2SingleSelectField {
+                        id
+                        name
+                        options {
+                            id
+                            name
+                        }
+                    }
+                }
+                fields(first:100) {
+                    nodes {
+                        ...ProjectFieldDetails
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+// The details we want about every field in a project.
+const PROJECT_FIELD_DETAILS: &str = r#"
+    fragment ProjectFieldDetails on ProjectV2FieldConfiguration {
+        ... on ProjectV2FieldCommon {
+            id
+            name
+            data_type: dataType
+        }
+        ... on ProjectV2SingleSelectField {
+            options {
+                id
+                name
+            }
+        }
+        ... on ProjectV2IterationField {
+            configuration {
+                iterations {
+                    id
+                    title
+                    start_date: startDate
+                    duration
+                }
+            }
+        }
+    }
+"#;
+
 #[derive(Debug, serde::Deserialize)]
 pub struct Projects {
     pub tools: ToolsProject,
@@ -66,7 +124,16 @@ pub struct Projects {
 pub struct ToolsProject {
     pub id: String,
     pub number: usize,
-    pub status: Field
+    pub status: Field,
+    #[serde(deserialize_with = "deserialize_nodes")]
+    pub fields: Vec<ProjectField>
+}
+
+impl ToolsProject {
+    /// Find a field by name (ignoring case).
+    pub fn field(&self, name: &str) -> Option<&ProjectField> {
+        self.fields.iter().find(|f| f.name.eq_ignore_ascii_case(name))
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -84,6 +151,48 @@ pub struct Field {
     pub options: Vec<FieldOption>
 }
 
+/// Any field on a project.
+#[derive(Debug, serde::Deserialize)]
+pub struct ProjectField {
+    pub id: String,
+    pub name: String,
+    pub data_type: FieldDataType,
+    /// Only set for single select fields.
+    #[serde(default)]
+    pub options: Vec<FieldOption>,
+    /// Only set for iteration fields.
+    #[serde(default)]
+    pub configuration: Option<IterationConfiguration>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FieldDataType {
+    Text,
+    Number,
+    Date,
+    SingleSelect,
+    Iteration,
+    /// Fields like the title, assignees or labels, which come from the content.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct IterationConfiguration {
+    pub iterations: Vec<Iteration>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Iteration {
+    pub id: String,
+    pub title: String,
+    /// In the form `YYYY-MM-DD`.
+    pub start_date: String,
+    /// In days.
+    pub duration: i64,
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct FieldOption {
     pub id: String,
@@ -97,7 +206,7 @@ pub async fn run(api: &Api, org: &str, tools_project: usize, roadmap_project: us
         organization: Projects
     }
 
-    let res: QueryResult = api.query(PROJECTS_QUERY, variables!(
+    let res: QueryResult = api.query(&format!("{PROJECTS_QUERY}{PROJECT_FIELD_DETAILS}"), variables!(
         "org": org,
         "tools_project": tools_project,
         "roadmap_project": roadmap_project
@@ -105,3 +214,37 @@ pub async fn run(api: &Api, org: &str, tools_project: usize, roadmap_project: us
 
     Ok(res.organization)
 }
+
+/// Fetch the details of some other project which, like the tools project, has a "Status" field.
+pub async fn tools_project(api: &Api, org: &str, project: usize) -> Result<ToolsProject, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        organization: QueryOrganization
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryOrganization {
+        project: ToolsProject
+    }
+
+    let res: QueryResult = api.query(&format!("{PROJECT_QUERY}{PROJECT_FIELD_DETAILS}"), variables!(
+        "org": org,
+        "project": project
+    )).await?;
+
+    Ok(res.organization.project)
+}
+
+// Connections come back as `{ nodes: [..] }`, which we flatten to just the nodes.
+fn deserialize_nodes<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>
+{
+    #[derive(serde::Deserialize)]
+    struct Nodes<T> {
+        nodes: Vec<T>
+    }
+    let nodes: Nodes<T> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(nodes.nodes)
+}
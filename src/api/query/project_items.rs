@@ -1,31 +1,88 @@
-use crate::api::Api;
+use crate::api::{ Api, common::FieldValue };
+use crate::lock::LEASE_TITLE;
 use crate::variables;
+use std::collections::HashMap;
 
 const PROJECT_ITEMS: &str = r#"
     query ProjectItems($org:String!, $project_number:Int!, $cursor:String) {
         organization(login:$org) {
             project: projectV2(number:$project_number) {
-                items(first:100, after:$cursor) {
+                items(first:100, after:$cursor, orderBy:{field:POSITION, direction:ASC}) {
                     page_info: pageInfo {
                         end_cursor: endCursor
                         has_next_page: hasNextPage
                     }
                     nodes {
                         id
+                        is_archived: isArchived
                         content {
+                            __typename
                             ... on Node {
                                 id
                             }
+                            ... on Issue {
+                                updated_at: updatedAt
+                                number
+                                title
+                                created_at: createdAt
+                                last_comment: comments(last:1) {
+                                    nodes {
+                                        created_at: createdAt
+                                    }
+                                }
+                                milestone {
+                                    due_on: dueOn
+                                }
+                                ...ContentDetails
+                            }
+                            ... on PullRequest {
+                                updated_at: updatedAt
+                                number
+                                title
+                                created_at: createdAt
+                                last_comment: comments(last:1) {
+                                    nodes {
+                                        created_at: createdAt
+                                    }
+                                }
+                                last_commit: commits(last:1) {
+                                    nodes {
+                                        commit {
+                                            committed_date: committedDate
+                                        }
+                                    }
+                                }
+                                milestone {
+                                    due_on: dueOn
+                                }
+                                ...ContentDetails
+                            }
+                            ... on DraftIssue {
+                                title
+                            }
                         }
-                        field_values: fieldValues(last:100) {
+                        field_values: fieldValues(first:100) {
                             nodes {
+                                __typename
+                                ... on ProjectV2ItemFieldTextValue {
+                                    text
+                                    field { ...FieldDetails }
+                                }
+                                ... on ProjectV2ItemFieldNumberValue {
+                                    number
+                                    field { ...FieldDetails }
+                                }
+                                ... on ProjectV2ItemFieldDateValue {
+                                    date
+                                    field { ...FieldDetails }
+                                }
                                 ... on ProjectV2ItemFieldSingleSelectValue {
-                                    field {
-                                        ... on ProjectV2SingleSelectField {
-                                            name
-                                        }
-                                    }
                                     option_id: optionId
+                                    field { ...FieldDetails }
+                                }
+                                ... on ProjectV2ItemFieldIterationValue {
+                                    iteration_id: iterationId
+                                    field { ...FieldDetails }
                                 }
                             }
                         }
@@ -34,18 +91,114 @@ const PROJECT_ITEMS: &str = r#"
             }
         }
     }
+
+    fragment ContentDetails on Labelable {
+        labels(first:50) {
+            nodes {
+                name
+            }
+        }
+        ... on RepositoryNode {
+            repository {
+                name_with_owner: nameWithOwner
+            }
+        }
+    }
+
+    fragment FieldDetails on ProjectV2FieldConfiguration {
+        ... on ProjectV2FieldCommon {
+            id
+            name
+            data_type: dataType
+        }
+    }
 "#;
 
 #[derive(Debug)]
 pub struct ProjectItem {
     /// ID of the item itself:
     pub item_id: String,
-    /// ID of the content (eg issue, PR) of this item:
-    pub content_id: String,
+    /// ID of the content (eg issue, PR) of this item, or `None` if it's inaccessible:
+    pub content_id: Option<String>,
+    /// When the content was last updated (if it's an issue or PR):
+    pub content_updated_at: Option<time::OffsetDateTime>,
     /// So we know which column the item is in:
     pub status_field_value_id: Option<String>,
+    /// Archived items are hidden from the board but still belong to the project.
+    pub is_archived: bool,
+    /// Details about the issue, PR or draft issue on this item.
+    pub content: ProjectItemContent,
+    /// The values of every text, number, date, single select and iteration field which is
+    /// set on the item (apart from the title, which comes from the content), by field name.
+    pub fields: HashMap<String, ItemFieldValue>,
 }
 
+#[derive(Debug, Clone)]
+pub enum ProjectItemContent {
+    Issue(ContentDetails),
+    PullRequest(ContentDetails),
+    DraftIssue { title: String },
+    /// The content has been deleted, or moved somewhere that we don't have access to.
+    Inaccessible,
+}
+
+/// Details about an issue or PR.
+#[derive(Debug, Clone)]
+pub struct ContentDetails {
+    pub number: usize,
+    pub title: String,
+    /// In the form `owner/name`.
+    pub repository: String,
+    pub created_at: time::OffsetDateTime,
+    /// The last time that anything happened to this; it was updated, commented on or (for PRs) committed to.
+    pub last_activity_at: time::OffsetDateTime,
+    /// When the milestone that this is in is due, if it's in one with a due date.
+    pub milestone_due_on: Option<time::OffsetDateTime>,
+    /// Names of every label.
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ContentState {
+    Open,
+    Closed,
+    /// Only PRs can be merged.
+    Merged,
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemFieldValue {
+    pub field_id: String,
+    pub value: FieldValue,
+}
+
+impl ProjectItem {
+    /// Details about the issue or PR on this item, if it is one.
+    pub fn content_details(&self) -> Option<&ContentDetails> {
+        match &self.content {
+            ProjectItemContent::Issue(details) | ProjectItemContent::PullRequest(details) => Some(details),
+            ProjectItemContent::DraftIssue { .. } | ProjectItemContent::Inaccessible => None,
+        }
+    }
+
+    /// A short description of the item for logging, eg `paritytech/subxt#123 "Some title"`.
+    pub fn describe(&self) -> String {
+        match &self.content {
+            ProjectItemContent::Issue(details) | ProjectItemContent::PullRequest(details) => {
+                format!("{}#{} {:?}", details.repository, details.number, details.title)
+            },
+            ProjectItemContent::DraftIssue { title } => {
+                format!("draft issue {title:?}")
+            },
+            ProjectItemContent::Inaccessible => {
+                format!("inaccessible item {}", self.item_id)
+            }
+        }
+    }
+}
+
+/// Fetch every item in the project (apart from any leases), in the order that they appear on the board.
 pub async fn run(api: &Api, org: &str, project_number: usize) -> Result<Vec<ProjectItem>, anyhow::Error> {
     // The shape we want to deserialize to.
     #[derive(serde::Deserialize)]
@@ -73,29 +226,116 @@ pub async fn run(api: &Api, org: &str, project_number: usize) -> Result<Vec<Proj
     #[derive(serde::Deserialize)]
     struct QueryItem {
         id: String,
-        content: QueryItemContent,
+        is_archived: bool,
+        // This is null if the content was deleted or we can't see it.
+        content: Option<QueryItemContent>,
         field_values: QueryItemFieldValues
     }
     #[derive(serde::Deserialize)]
-    struct QueryItemContent {
-        id: String
+    #[serde(tag = "__typename")]
+    enum QueryItemContent {
+        Issue {
+            id: String,
+            #[serde(with = "time::serde::iso8601")]
+            updated_at: time::OffsetDateTime,
+            #[serde(flatten)]
+            details: QueryContentDetails
+        },
+        PullRequest {
+            id: String,
+            #[serde(with = "time::serde::iso8601")]
+            updated_at: time::OffsetDateTime,
+            #[serde(flatten)]
+            details: QueryContentDetails
+        },
+        DraftIssue {
+            id: String,
+            title: String
+        }
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryContentDetails {
+        number: usize,
+        title: String,
+        #[serde(with = "time::serde::iso8601")]
+        created_at: time::OffsetDateTime,
+        last_comment: QueryNames<QueryCreatedAt>,
+        // Only PRs have commits.
+        #[serde(default)]
+        last_commit: Option<QueryNames<QueryCommit>>,
+        milestone: Option<QueryMilestone>,
+        repository: QueryRepository,
+        labels: QueryNames<QueryLabel>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCreatedAt {
+        #[serde(with = "time::serde::iso8601")]
+        created_at: time::OffsetDateTime
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCommit {
+        commit: QueryCommitDate
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCommitDate {
+        #[serde(with = "time::serde::iso8601")]
+        committed_date: time::OffsetDateTime
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryMilestone {
+        #[serde(default, with = "time::serde::iso8601::option")]
+        due_on: Option<time::OffsetDateTime>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRepository {
+        name_with_owner: String
     }
     #[derive(serde::Deserialize)]
+    struct QueryNames<T> {
+        nodes: Vec<T>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryLabel {
+        name: String
+    }
+    let to_details = |details: QueryContentDetails, updated_at: time::OffsetDateTime| ContentDetails {
+        last_activity_at: details.last_comment.nodes
+            .iter()
+            .map(|c| c.created_at)
+            .chain(details.last_commit.iter().flat_map(|c| &c.nodes).map(|c| c.commit.committed_date))
+            .fold(updated_at, std::cmp::max),
+        number: details.number,
+        title: details.title,
+        repository: details.repository.name_with_owner,
+        created_at: details.created_at,
+        milestone_due_on: details.milestone.and_then(|m| m.due_on),
+        labels: details.labels.nodes.into_iter().map(|n| n.name).collect(),
+    };
+    #[derive(serde::Deserialize)]
     struct QueryItemFieldValues {
         nodes: Vec<QueryItemFieldValue>
     }
     #[derive(serde::Deserialize, Debug)]
-    #[serde(untagged)]
+    #[serde(tag = "__typename")]
     enum QueryItemFieldValue {
-        SingleSelectField {
-            option_id: String,
-            field: QueryItemFieldDetails
-        },
-        Unknown {}
+        #[serde(rename = "ProjectV2ItemFieldTextValue")]
+        Text { text: String, field: QueryItemFieldDetails },
+        #[serde(rename = "ProjectV2ItemFieldNumberValue")]
+        Number { number: f64, field: QueryItemFieldDetails },
+        #[serde(rename = "ProjectV2ItemFieldDateValue")]
+        Date { date: String, field: QueryItemFieldDetails },
+        #[serde(rename = "ProjectV2ItemFieldSingleSelectValue")]
+        SingleSelect { option_id: String, field: QueryItemFieldDetails },
+        #[serde(rename = "ProjectV2ItemFieldIterationValue")]
+        Iteration { iteration_id: String, field: QueryItemFieldDetails },
+        #[serde(other)]
+        Unknown
     }
     #[derive(serde::Deserialize, Debug)]
     struct QueryItemFieldDetails {
-        name: String
+        id: String,
+        name: String,
+        data_type: String
     }
 
     let mut items = Vec::new();
@@ -108,18 +348,58 @@ pub async fn run(api: &Api, org: &str, project_number: usize) -> Result<Vec<Proj
         )).await?;
 
         for item in res.organization.project.items.nodes {
-            items.push(ProjectItem {
-                content_id: item.content.id,
-                item_id: item.id,
-                status_field_value_id: item.field_values.nodes.into_iter().find_map(|n| {
-                    // Only return the field value ID if we find the status field in our fields list:
-                    match n {
-                        QueryItemFieldValue::SingleSelectField { option_id, field } if field.name == "Status" => {
-                            Some(option_id)
-                        }
-                        _ => None
+            // Leases which stop syncs from overlapping live on the board too, but aren't ours to manage.
+            if matches!(&item.content, Some(QueryItemContent::DraftIssue { title, .. }) if title == LEASE_TITLE) {
+                continue
+            }
+            let fields: HashMap<String, ItemFieldValue> = item.field_values.nodes
+                .into_iter()
+                .filter_map(|n| {
+                    let (field, value) = match n {
+                        QueryItemFieldValue::Text { text, field } => (field, FieldValue::Text(text)),
+                        QueryItemFieldValue::Number { number, field } => (field, FieldValue::Number(number)),
+                        QueryItemFieldValue::Date { date, field } => (field, FieldValue::Date(date)),
+                        QueryItemFieldValue::SingleSelect { option_id, field } => (field, FieldValue::SingleSelect(option_id)),
+                        QueryItemFieldValue::Iteration { iteration_id, field } => (field, FieldValue::Iteration(iteration_id)),
+                        QueryItemFieldValue::Unknown => return None
+                    };
+                    // The title is a text field, but it comes from the issue/PR and can't be set.
+                    if field.data_type == "TITLE" {
+                        return None
                     }
+                    Some((field.name, ItemFieldValue { field_id: field.id, value }))
                 })
+                .collect();
+
+            // Only return the field value ID if we find the status field in our fields list:
+            let status_field_value_id = match fields.get("Status").map(|f| &f.value) {
+                Some(FieldValue::SingleSelect(option_id)) => Some(option_id.clone()),
+                _ => None
+            };
+
+            let (content_id, content_updated_at, content) = match item.content {
+                Some(QueryItemContent::Issue { id, updated_at, details }) => {
+                    (Some(id), Some(updated_at), ProjectItemContent::Issue(to_details(details, updated_at)))
+                },
+                Some(QueryItemContent::PullRequest { id, updated_at, details }) => {
+                    (Some(id), Some(updated_at), ProjectItemContent::PullRequest(to_details(details, updated_at)))
+                },
+                Some(QueryItemContent::DraftIssue { id, title }) => {
+                    (Some(id), None, ProjectItemContent::DraftIssue { title })
+                },
+                None => {
+                    (None, None, ProjectItemContent::Inaccessible)
+                }
+            };
+
+            items.push(ProjectItem {
+                content_id,
+                content_updated_at,
+                item_id: item.id,
+                status_field_value_id,
+                is_archived: item.is_archived,
+                content,
+                fields
             })
         }
 
@@ -129,6 +409,5 @@ pub async fn run(api: &Api, org: &str, project_number: usize) -> Result<Vec<Proj
         }
     }
 
-
     Ok(items)
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+use crate::api::Api;
+use crate::variables;
+
+const PROJECT_LEASES: &str = r#"
+    query ProjectLeases($org:String!, $project_number:Int!, $cursor:String) {
+        organization(login:$org) {
+            project: projectV2(number:$project_number) {
+                id
+                items(first:100, after:$cursor) {
+                    page_info: pageInfo {
+                        end_cursor: endCursor
+                        has_next_page: hasNextPage
+                    }
+                    nodes {
+                        id
+                        content {
+                            ... on DraftIssue {
+                                title
+                                created_at: createdAt
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+#[derive(Debug)]
+pub struct ProjectLeases {
+    /// ID of the project.
+    pub project_id: String,
+    /// Any draft issues on the project whose title matches the one given.
+    pub leases: Vec<Lease>,
+}
+
+#[derive(Debug)]
+pub struct Lease {
+    pub item_id: String,
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Find the draft issues on a project with a given title. These are used as leases to
+/// stop syncs on different machines from running at the same time.
+pub async fn run(api: &Api, org: &str, project_number: usize, lease_title: &str) -> Result<ProjectLeases, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        organization: QueryProject
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryProject {
+        project: QueryItems
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryItems {
+        id: String,
+        items: QueryNodes
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryNodes {
+        nodes: Vec<QueryItem>,
+        page_info: QueryPageInfo
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryPageInfo {
+        end_cursor: Option<String>,
+        has_next_page: bool
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryItem {
+        id: String,
+        content: Option<QueryItemContent>
+    }
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum QueryItemContent {
+        DraftIssue {
+            title: String,
+            #[serde(with = "time::serde::iso8601")]
+            created_at: time::OffsetDateTime
+        },
+        Unknown {}
+    }
+
+    let mut leases = Vec::new();
+    let mut cursor = None;
+    let project_id = loop {
+        let res: QueryResult = api.query(PROJECT_LEASES, variables!(
+            "org": org,
+            "project_number": project_number,
+            "cursor": cursor
+        )).await?;
+
+        for item in res.organization.project.items.nodes {
+            if let Some(QueryItemContent::DraftIssue { title, created_at }) = item.content {
+                if title == lease_title {
+                    leases.push(Lease { item_id: item.id, created_at });
+                }
+            }
+        }
+
+        cursor = res.organization.project.items.page_info.end_cursor;
+        if !res.organization.project.items.page_info.has_next_page || cursor.is_none() {
+            break res.organization.project.id
+        }
+    };
+
+    Ok(ProjectLeases { project_id, leases })
+}
@@ -8,29 +8,58 @@ const PROJECT_ISSUES_QUERY: &str = r#"
             id
             issues(first:100, orderBy:{field:UPDATED_AT, direction:DESC}) {
                 nodes {
-                    id
-                    title
-                    state
-                    body
-                    projectItems(last:100) {
-                        nodes {
-                            id
-                            project {
-                                number
-                            }
-                            status: fieldValueByName(name:"Status") {
-                                ... on ProjectV2ItemFieldSingleSelectValue {
-                                    optionId
-                                }
-                            }
-                            deadline: fieldValueByName(name:"Deadline") {
-                                ... on ProjectV2ItemFieldSingleSelectValue {
-                                    optionId
-                                }
-                            }
-                            team: fieldValueByName(name:"Team") {
-                                ... on ProjectV2ItemFieldSingleSelectValue {
-                                    optionId
+                    ...ProjectIssueFields
+                }
+            }
+        }
+    }
+"#;
+
+const ISSUES_BY_ID_QUERY: &str = r#"
+    query ProjectIssuesById($ids: [ID!]!) {
+        nodes(ids: $ids) {
+            ... on Issue {
+                ...ProjectIssueFields
+            }
+        }
+    }
+"#;
+
+const PROJECT_ISSUE_FIELDS: &str = r#"
+    fragment ProjectIssueFields on Issue {
+        id
+        title
+        state
+        body
+        projectItems(last:100) {
+            nodes {
+                id
+                is_archived: isArchived
+                project {
+                    number
+                }
+                status: fieldValueByName(name:"Status") {
+                    ... on ProjectV2ItemFieldSingleSelectValue {
+                        optionId
+                    }
+                }
+                deadline: fieldValueByName(name:"Deadline") {
+                    ... on ProjectV2ItemFieldSingleSelectValue {
+                        optionId
+                    }
+                }
+                team: fieldValueByName(name:"Team") {
+                    ... on ProjectV2ItemFieldSingleSelectValue {
+                        optionId
+                    }
+                }
+                date_values: fieldValues(first:50) {
+                    nodes {
+                        ... on ProjectV2ItemFieldDateValue {
+                            date
+                            field {
+                                ... on ProjectV2FieldCommon {
+                                    id
                                 }
                             }
                         }
@@ -62,7 +91,10 @@ pub struct ProjectIssue {
 #[derive(Debug)]
 pub struct ToolsProject {
     pub item_id: String,
-    pub status_id: Option<String>
+    pub is_archived: bool,
+    pub status_id: Option<String>,
+    /// Values of any date fields on the item, by field ID.
+    pub dates: HashMap<String, String>
 }
 
 #[derive(Debug)]
@@ -73,6 +105,54 @@ pub struct RoadmapProject {
     pub team_id: Option<String>,
 }
 
+// The shape we want to deserialize issues to.
+#[derive(serde::Deserialize)]
+struct QueryIssue {
+    id: String,
+    title: String,
+    state: crate::api::common::State,
+    #[serde(rename = "body")]
+    body_text: String,
+    #[serde(rename = "projectItems")]
+    project_items: QueryProjectItems
+}
+#[derive(serde::Deserialize)]
+struct QueryProjectItems {
+    nodes: Vec<QueryProjectItem>
+}
+#[derive(serde::Deserialize)]
+struct QueryProjectItem {
+    id: String,
+    is_archived: bool,
+    project: QueryProjectNumber,
+    date_values: QueryDateValues,
+    // Projects that aren't the ones we're looking for
+    // might have all sorts of random stuff, so be flexible
+    // here.
+    #[serde(flatten)]
+    rest: HashMap<String, serde_json::Value>,
+}
+#[derive(serde::Deserialize)]
+struct QueryDateValues {
+    nodes: Vec<QueryDateValue>
+}
+#[derive(serde::Deserialize)]
+struct QueryDateValue {
+    // Values which aren't dates come back empty.
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    field: Option<QueryFieldId>
+}
+#[derive(serde::Deserialize)]
+struct QueryFieldId {
+    id: String
+}
+#[derive(serde::Deserialize)]
+struct QueryProjectNumber {
+    number: usize
+}
+
 pub async fn run(api: &Api, org: &str, repo_name: &str, tools_project: usize, roadmap_project: usize) -> Result<ProjectRepo, anyhow::Error> {
     // The shape we want to deserialize to.
     #[derive(serde::Deserialize)]
@@ -88,80 +168,89 @@ pub async fn run(api: &Api, org: &str, repo_name: &str, tools_project: usize, ro
     struct QueryIssues {
         nodes: Vec<QueryIssue>
     }
-    #[derive(serde::Deserialize)]
-    struct QueryIssue {
-        id: String,
-        title: String,
-        state: crate::api::common::State,
-        #[serde(rename = "body")]
-        body_text: String,
-        #[serde(rename = "projectItems")]
-        project_items: QueryProjectItems
-    }
-    #[derive(serde::Deserialize)]
-    struct QueryProjectItems {
-        nodes: Vec<QueryProjectItem>
-    }
-    #[derive(serde::Deserialize)]
-    struct QueryProjectItem {
-        id: String,
-        project: QueryProjectNumber,
-        // Projects that aren't the ones we're looking for
-        // might have all sorts of random stuff, so be flexible
-        // here.
-        #[serde(flatten)]
-        rest: HashMap<String, serde_json::Value>,
-    }
-    #[derive(serde::Deserialize)]
-    struct QueryProjectNumber {
-        number: usize
-    }
 
-    let res: QueryResult = api.query(PROJECT_ISSUES_QUERY, variables!(
+    let query = format!("{PROJECT_ISSUES_QUERY}{PROJECT_ISSUE_FIELDS}");
+    let res: QueryResult = api.query(&query, variables!(
         "org": org,
         "repo": repo_name
     )).await?;
 
-    let issues = res.repository.issues.nodes.into_iter().map(|issue| {
-        let tools_project = issue.project_items.nodes.iter().find(|item| {
-            item.project.number == tools_project
-        });
-        let roadmap_project = issue.project_items.nodes.iter().find(|item| {
-            item.project.number == roadmap_project
-        });
-
-        let get_field_id = |map: &HashMap<String,serde_json::Value>, field: &str| {
-            map.get(field)
-                .and_then(|s| s.as_object())
-                .and_then(|s| s.get("optionId"))
-                .and_then(|n| n.as_str())
-                .map(|n| n.to_owned())
-        };
-
-        ProjectIssue {
-            description: issue.body_text,
-            id: issue.id,
-            state: issue.state,
-            title: issue.title,
-            tools_project: tools_project.map(|p| {
-                ToolsProject {
-                    item_id: p.id.clone(),
-                    status_id: get_field_id(&p.rest, "status")
-                }
-            }),
-            roadmap_project: roadmap_project.map(|p| {
-                RoadmapProject {
-                    item_id: p.id.clone(),
-                    status_id: get_field_id(&p.rest, "status"),
-                    deadline_id: get_field_id(&p.rest, "deadline"),
-                    team_id: get_field_id(&p.rest, "team")
-                }
-            }),
-        }
-    });
+    let issues = res.repository.issues.nodes
+        .into_iter()
+        .map(|issue| to_project_issue(issue, tools_project, roadmap_project));
 
     Ok(ProjectRepo {
         id: res.repository.id,
         issues: issues.collect()
     })
 }
+
+/// Fetch specific issues given their IDs. Any IDs which don't point to an issue
+/// (for instance because it's been deleted) are ignored.
+pub async fn issues_by_id(api: &Api, issue_ids: &[String], tools_project: usize, roadmap_project: usize) -> Result<Vec<ProjectIssue>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        nodes: Vec<Option<QueryIssue>>
+    }
+
+    let query = format!("{ISSUES_BY_ID_QUERY}{PROJECT_ISSUE_FIELDS}");
+    let mut issues = Vec::new();
+    // We can ask for up to 100 nodes at a time:
+    for ids in issue_ids.chunks(100) {
+        let res: QueryResult = api.query(&query, variables!(
+            "ids": ids
+        )).await?;
+
+        let new_issues = res.nodes
+            .into_iter()
+            .flatten()
+            .map(|issue| to_project_issue(issue, tools_project, roadmap_project));
+        issues.extend(new_issues);
+    }
+
+    Ok(issues)
+}
+
+fn to_project_issue(issue: QueryIssue, tools_project: usize, roadmap_project: usize) -> ProjectIssue {
+    let tools_project = issue.project_items.nodes.iter().find(|item| {
+        item.project.number == tools_project
+    });
+    let roadmap_project = issue.project_items.nodes.iter().find(|item| {
+        item.project.number == roadmap_project
+    });
+
+    let get_field_id = |map: &HashMap<String,serde_json::Value>, field: &str| {
+        map.get(field)
+            .and_then(|s| s.as_object())
+            .and_then(|s| s.get("optionId"))
+            .and_then(|n| n.as_str())
+            .map(|n| n.to_owned())
+    };
+
+    ProjectIssue {
+        description: issue.body_text,
+        id: issue.id,
+        state: issue.state,
+        title: issue.title,
+        tools_project: tools_project.map(|p| {
+            ToolsProject {
+                item_id: p.id.clone(),
+                is_archived: p.is_archived,
+                status_id: get_field_id(&p.rest, "status"),
+                dates: p.date_values.nodes
+                    .iter()
+                    .filter_map(|v| Some((v.field.as_ref()?.id.clone(), v.date.clone()?)))
+                    .collect()
+            }
+        }),
+        roadmap_project: roadmap_project.map(|p| {
+            RoadmapProject {
+                item_id: p.id.clone(),
+                status_id: get_field_id(&p.rest, "status"),
+                deadline_id: get_field_id(&p.rest, "deadline"),
+                team_id: get_field_id(&p.rest, "team")
+            }
+        }),
+    }
+}
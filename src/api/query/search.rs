@@ -0,0 +1,61 @@
+use crate::api::Api;
+use crate::variables;
+
+const SEARCH_QUERY: &str = r#"
+    query Search($query:String!, $cursor:String) {
+        search(after:$cursor, first:100, query:$query, type:ISSUE) {
+            nodes {
+                ... on Issue {
+                    id
+                }
+                ... on PullRequest {
+                    id
+                }
+            }
+            page: pageInfo {
+                has_next_page: hasNextPage
+                cursor: endCursor
+            }
+        }
+    }
+"#;
+
+/// Run an arbitrary issue/PR search, returning the IDs of everything that's found.
+pub async fn run(api: &Api, query: &str) -> Result<Vec<String>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        search: QuerySearch
+    }
+    #[derive(serde::Deserialize)]
+    struct QuerySearch {
+        nodes: Vec<QueryItem>,
+        page: PageInfo
+    }
+    #[derive(serde::Deserialize)]
+    struct PageInfo {
+        has_next_page: bool,
+        cursor: Option<String>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryItem {
+        id: String
+    }
+
+    let mut cursor = None;
+    let mut ids = Vec::new();
+    loop {
+        let res: QueryResult = api.query(SEARCH_QUERY, variables!(
+            "query": query,
+            "cursor": cursor
+        )).await?;
+
+        ids.extend(res.search.nodes.into_iter().map(|n| n.id));
+
+        cursor = res.search.page.cursor;
+        if !res.search.page.has_next_page || cursor.is_none() {
+            break;
+        }
+    }
+    Ok(ids)
+}
@@ -0,0 +1,209 @@
+use crate::api::Api;
+use crate::variables;
+
+const TEAM_MEMBERS_QUERY: &str = r#"
+    query TeamMembers($org:String!, $slug:String!, $cursor:String) {
+        organization(login:$org) {
+            team(slug:$slug) {
+                members(first:100, after:$cursor) {
+                    nodes {
+                        login
+                    }
+                    page_info: pageInfo {
+                        end_cursor: endCursor
+                        has_next_page: hasNextPage
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+const TEAM_REPOS_QUERY: &str = r#"
+    query TeamRepos($org:String!, $slug:String!, $cursor:String) {
+        organization(login:$org) {
+            team(slug:$slug) {
+                repositories(first:100, after:$cursor) {
+                    edges {
+                        permission
+                        node {
+                            name
+                            owner {
+                                login
+                            }
+                            is_archived: isArchived
+                            topics: repositoryTopics(first:20) {
+                                nodes {
+                                    topic {
+                                        name
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    page_info: pageInfo {
+                        end_cursor: endCursor
+                        has_next_page: hasNextPage
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+/// A repository that a team has access to.
+#[derive(Debug, Clone)]
+pub struct TeamRepo {
+    pub name: String,
+    pub owner: String,
+    pub is_archived: bool,
+    /// The team's permission on the repo.
+    pub permission: RepoPermission,
+    pub topics: Vec<String>,
+}
+
+/// Permissions, from least to most permissive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoPermission {
+    #[serde(alias = "READ")]
+    Read,
+    #[serde(alias = "TRIAGE")]
+    Triage,
+    #[serde(alias = "WRITE")]
+    Write,
+    #[serde(alias = "MAINTAIN")]
+    Maintain,
+    #[serde(alias = "ADMIN")]
+    Admin,
+}
+
+#[derive(serde::Deserialize)]
+struct QueryPageInfo {
+    end_cursor: Option<String>,
+    has_next_page: bool
+}
+
+/// The logins of every member of the team with the given slug in the given org.
+pub async fn members(api: &Api, org: &str, slug: &str) -> Result<Vec<String>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        organization: QueryOrganization
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryOrganization {
+        team: Option<QueryTeam>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryTeam {
+        members: QueryMembers
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryMembers {
+        nodes: Vec<QueryMember>,
+        page_info: QueryPageInfo
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryMember {
+        login: String
+    }
+
+    let mut members = Vec::new();
+    let mut cursor = None;
+    loop {
+        let res: QueryResult = api.query(TEAM_MEMBERS_QUERY, variables!(
+            "org": org,
+            "slug": slug,
+            "cursor": cursor
+        )).await?;
+        let Some(team) = res.organization.team else {
+            anyhow::bail!("Could not find the team '{org}/{slug}'")
+        };
+
+        members.extend(team.members.nodes.into_iter().map(|m| m.login));
+
+        cursor = team.members.page_info.end_cursor;
+        if !team.members.page_info.has_next_page || cursor.is_none() {
+            break
+        }
+    }
+    Ok(members)
+}
+
+/// Every repository that the team with the given slug in the given org has access to.
+pub async fn repos(api: &Api, org: &str, slug: &str) -> Result<Vec<TeamRepo>, anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        organization: QueryOrganization
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryOrganization {
+        team: Option<QueryTeam>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryTeam {
+        repositories: QueryRepositories
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRepositories {
+        edges: Vec<QueryEdge>,
+        page_info: QueryPageInfo
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryEdge {
+        permission: RepoPermission,
+        node: QueryRepository
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRepository {
+        name: String,
+        owner: QueryOwner,
+        is_archived: bool,
+        topics: QueryTopics
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryOwner {
+        login: String
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryTopics {
+        nodes: Vec<QueryTopicNode>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryTopicNode {
+        topic: QueryTopic
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryTopic {
+        name: String
+    }
+
+    let mut repos = Vec::new();
+    let mut cursor = None;
+    loop {
+        let res: QueryResult = api.query(TEAM_REPOS_QUERY, variables!(
+            "org": org,
+            "slug": slug,
+            "cursor": cursor
+        )).await?;
+        let Some(team) = res.organization.team else {
+            anyhow::bail!("Could not find the team '{org}/{slug}'")
+        };
+
+        repos.extend(team.repositories.edges.into_iter().map(|edge| TeamRepo {
+            name: edge.node.name,
+            owner: edge.node.owner.login,
+            is_archived: edge.node.is_archived,
+            permission: edge.permission,
+            topics: edge.node.topics.nodes.into_iter().map(|t| t.topic.name).collect(),
+        }));
+
+        cursor = team.repositories.page_info.end_cursor;
+        if !team.repositories.page_info.has_next_page || cursor.is_none() {
+            break
+        }
+    }
+    Ok(repos)
+}
@@ -1,87 +1,780 @@
-use crate::api::Api;
+use crate::api::{ Api, common::{ orgs_qualifier, repos_qualifier, updated_since_qualifier } };
 use crate::variables;
-use std::collections::HashSet;
+use regex::Regex;
+use std::collections::{ HashMap, HashSet };
 
 const NEEDS_REVIEW: &str = r#"
-    query TeamPullRequests($mentions_query:String!, $assigned_query:String!, $team_query:String!) {
+    query TeamPullRequests($mentions_query:String!, $reviewed_query:String!, $assigned_query:String!, $team_query:String!) {
         mentions: search(last:100, query:$mentions_query, type:ISSUE) {
             nodes {
-                ... on PullRequest {
-                    id,
-                    draft: isDraft
-                }
+                ...PrDetails
+            }
+        }
+        reviewed: search(last:100, query:$reviewed_query, type:ISSUE) {
+            nodes {
+                ...PrDetails
             }
         }
         assigned: search(last:100, query:$assigned_query, type:ISSUE) {
             nodes {
-                ... on PullRequest {
-                    id,
-                    draft: isDraft
-                }
+                ...PrDetails
             }
         }
         team: search(last:100, query:$team_query, type:ISSUE) {
             nodes {
-                ... on PullRequest {
-                    id,
-                    draft: isDraft
+                ...PrDetails
+            }
+        }
+    }
+"#;
+
+const USER_REVIEW_REQUESTED: &str = r#"
+    query UserReviewRequestedPullRequests($query:String!) {
+        search(last:100, query:$query, type:ISSUE) {
+            nodes {
+                ...PrDetails
+            }
+        }
+    }
+"#;
+
+// How many users to look for review requests for in each search. GitHub limits the length
+// of search queries, so we can't ask about every team member at once.
+const USERS_PER_SEARCH: usize = 5;
+
+const CODE_OWNERS: &str = r#"
+    query CodeOwners($owner:String!, $name:String!) {
+        repository(owner:$owner, name:$name) {
+            codeowners: object(expression:"HEAD:.github/CODEOWNERS") {
+                ... on Blob {
+                    text
+                }
+            }
+            root_codeowners: object(expression:"HEAD:CODEOWNERS") {
+                ... on Blob {
+                    text
+                }
+            }
+            docs_codeowners: object(expression:"HEAD:docs/CODEOWNERS") {
+                ... on Blob {
+                    text
                 }
             }
         }
     }
 "#;
 
-pub async fn run(api: &Api, org: &str, team_group_name: &str, team_members:&[String], team_repos:&[String]) -> Result<Vec<Issue>, anyhow::Error> {
+const CODE_OWNED: &str = r#"
+    query CodeOwnedPullRequests($owner:String!, $name:String!, $cursor:String) {
+        repository(owner:$owner, name:$name) {
+            pull_requests: pullRequests(states:OPEN, first:50, after:$cursor, orderBy:{field:UPDATED_AT, direction:DESC}) {
+                nodes {
+                    ...PrDetails
+                    updated_at: updatedAt
+                    files(first:100) {
+                        nodes {
+                            path
+                        }
+                    }
+                }
+                page_info: pageInfo {
+                    end_cursor: endCursor
+                    has_next_page: hasNextPage
+                }
+            }
+        }
+    }
+"#;
+
+// How many PRs to check the required checks of in each query.
+const PRS_PER_REQUIRED_CHECKS_QUERY: usize = 20;
+
+// Whether a check or status is required depends on the PR (its base branch in particular),
+// so this is asked about each PR separately, rather than as part of the PR details.
+const REQUIRED_CHECKS_PR: &str = r#"
+        pr{n}: node(id:$id{n}) {
+            ... on PullRequest {
+                id
+                head_commit: commits(last:1) {
+                    nodes {
+                        commit {
+                            status_check_rollup: statusCheckRollup {
+                                contexts(first:100) {
+                                    nodes {
+                                        __typename
+                                        ... on CheckRun {
+                                            conclusion
+                                            is_required: isRequired(pullRequestId:$id{n})
+                                        }
+                                        ... on StatusContext {
+                                            state
+                                            is_required: isRequired(pullRequestId:$id{n})
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+"#;
+
+// The details we want about every PR.
+const PR_DETAILS: &str = r#"
+    fragment PrDetails on PullRequest {
+        id
+        draft: isDraft
+        review_decision: reviewDecision
+        mergeable
+        head_commit: commits(last:1) {
+            nodes {
+                commit {
+                    status_check_rollup: statusCheckRollup {
+                        state
+                    }
+                }
+            }
+        }
+        latest_reviews: latestReviews(first:20) {
+            nodes {
+                state
+            }
+        }
+        review_requests: reviewRequests(first:20) {
+            nodes {
+                requested_reviewer: requestedReviewer {
+                    ...ReviewerDetails
+                }
+            }
+        }
+        review_requested_events: timelineItems(first:50, itemTypes:[REVIEW_REQUESTED_EVENT]) {
+            nodes {
+                ... on ReviewRequestedEvent {
+                    requested_reviewer: requestedReviewer {
+                        ...ReviewerDetails
+                    }
+                }
+            }
+        }
+    }
+
+    fragment ReviewerDetails on RequestedReviewer {
+        __typename
+        ... on User {
+            login
+        }
+        ... on Team {
+            combined_slug: combinedSlug
+        }
+    }
+"#;
+
+pub async fn run(api: &Api, orgs: &[String], team_group_name: &str, team_members:&[String], team_repos:&[String], updated_since: Option<time::OffsetDateTime>) -> Result<Vec<Issue>, anyhow::Error> {
     // The shape we want to deserialize to.
     #[derive(serde::Deserialize)]
     struct QueryResult {
-        mentions: QuerySearch,
-        assigned: QuerySearch,
-        team: QuerySearch
-    }
-    #[derive(serde::Deserialize)]
-    struct QuerySearch {
-        nodes: Vec<Issue>
+        mentions: QueryNodes<QueryPr>,
+        reviewed: QueryNodes<QueryPr>,
+        assigned: QueryNodes<QueryPr>,
+        team: QueryNodes<QueryPr>
     }
 
-    // Find all PRs where the tools team is an assigned reviewer. These will disappear once anybody has reviewed them but might at least
-    // help to catch some PRs we've been asked to review (perhaps on external repos).
-    let assigned_query = format!("is:pr is:open sort:updated-desc org:{org} team-review-requested:{team_group_name}");
-    // Find all PRs where our team group is in the body (why? because if you request a review from a team, the team disappears as soon as one
-    // person has reviewed the PR, and that's no good becasue we want the PR to show up until merged)
-    let mentions_query = format!("is:pr is:open sort:updated-desc org:{org} in:body '{team_group_name}'");
-    // Find all PRs that are authored by team members in team controlled repos.
-    let mut team_repos_query = String::new();
-    for repo in team_repos {
-        team_repos_query.push_str(" repo:");
-        team_repos_query.push_str(org);
-        team_repos_query.push_str("/");
-        team_repos_query.push_str(repo);
+    // If given, only look for PRs updated since some date.
+    let updated_query = updated_since_qualifier(updated_since);
+    let orgs_query = orgs_qualifier(orgs);
+
+    // Find all PRs where the tools team is an assigned reviewer (perhaps on external repos). The team is no longer an assigned reviewer
+    // once somebody from it has reviewed the PR, which is where the next search comes in.
+    let assigned_query = format!("is:pr is:open sort:updated-desc {orgs_query} {updated_query} team-review-requested:{team_group_name}");
+    // Find all PRs that team members have reviewed. Requesting a review from a team only lasts until one person from it has reviewed the PR,
+    // so we look back through the timelines of these to find those which asked the team group for review at some point; we want those to
+    // show up until merged.
+    let mut reviewers_query = String::new();
+    for name in team_members {
+        reviewers_query.push_str(" reviewed-by:");
+        reviewers_query.push_str(name);
     }
+    let reviewed_query = format!("is:pr is:open sort:updated-desc {orgs_query} {updated_query} {reviewers_query}");
+    // Find all PRs where our team group is in the body. This is no longer needed to keep PRs which asked the team for review
+    // around, but lets people pull in the team without formally requesting a review.
+    let mentions_query = format!("is:pr is:open sort:updated-desc {orgs_query} {updated_query} in:body '{team_group_name}'");
+    // Find all PRs that are authored by team members in team controlled repos.
+    let team_repos_query = repos_qualifier(team_repos);
     let mut team_members_query = String::new();
     for name in team_members {
         team_members_query.push_str(" author:");
-        team_members_query.push_str(&name);
+        team_members_query.push_str(name);
     }
-    let team_query = format!("is:pr is:open {team_repos_query} {team_members_query}");
+    let team_query = format!("is:pr is:open {updated_query} {team_repos_query} {team_members_query}");
 
-    let res: QueryResult = api.query(NEEDS_REVIEW, variables!(
+    let res: QueryResult = api.query(&format!("{NEEDS_REVIEW}{PR_DETAILS}"), variables!(
         "assigned_query": assigned_query,
         "mentions_query": mentions_query,
+        "reviewed_query": reviewed_query,
         "team_query": team_query
     )).await?;
 
+    // Find all PRs which ask team members themselves for review, anywhere in our orgs.
+    #[derive(serde::Deserialize)]
+    struct UserQueryResult {
+        search: QueryNodes<QueryPr>
+    }
+    let mut user_review_requested = Vec::new();
+    for names in team_members.chunks(USERS_PER_SEARCH) {
+        let mut users_query = String::new();
+        for name in names {
+            users_query.push_str(" user-review-requested:");
+            users_query.push_str(name);
+        }
+        let query = format!("is:pr is:open sort:updated-desc {orgs_query} {updated_query} {users_query}");
+        let res: UserQueryResult = api.query(&format!("{USER_REVIEW_REQUESTED}{PR_DETAILS}"), variables!(
+            "query": query
+        )).await?;
+        user_review_requested.extend(res.search.nodes);
+    }
+
+    let reviewed = res.reviewed.nodes
+        .into_iter()
+        .filter(|pr| pr.requested_review_from_team(team_group_name));
+
     // Remove any dupes:
-    let set: HashSet<Issue> = res.team.nodes.into_iter()
+    let mut seen = HashSet::new();
+    let mut prs: Vec<Issue> = res.team.nodes.into_iter()
         .chain(res.assigned.nodes)
         .chain(res.mentions.nodes)
+        .chain(reviewed)
+        .chain(user_review_requested)
+        .filter(|pr| seen.insert(pr.id.clone()))
+        .map(QueryPr::into_issue)
         .collect();
 
-    Ok(set.into_iter().collect())
+    find_failing_required_checks(api, &mut prs).await?;
+    Ok(prs)
+}
+
+/// Find open PRs in the given repos (in the form `owner/name`) which change files that the CODEOWNERS
+/// file of the repo says belong to any of the given owners (users, or teams given as `org/team`).
+pub async fn code_owned(api: &Api, repos: &[String], owners: &[&str], updated_since: Option<time::OffsetDateTime>) -> Result<Vec<Issue>, anyhow::Error> {
+    // The shapes we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct CodeOwnersQueryResult {
+        repository: QueryCodeOwners
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCodeOwners {
+        codeowners: Option<QueryBlob>,
+        root_codeowners: Option<QueryBlob>,
+        docs_codeowners: Option<QueryBlob>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryBlob {
+        text: Option<String>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryResult {
+        repository: QueryRepository
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRepository {
+        pull_requests: QueryPullRequests
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryPullRequests {
+        nodes: Vec<QueryCodeOwnedPr>,
+        page_info: QueryPageInfo
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryPageInfo {
+        end_cursor: Option<String>,
+        has_next_page: bool
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCodeOwnedPr {
+        #[serde(flatten)]
+        pr: QueryPr,
+        #[serde(with = "time::serde::iso8601")]
+        updated_at: time::OffsetDateTime,
+        files: QueryNodes<QueryFile>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryFile {
+        path: String
+    }
+
+    let query = format!("{CODE_OWNED}{PR_DETAILS}");
+    let mut prs = Vec::new();
+    for repo in repos {
+        let Some((owner, name)) = repo.split_once('/') else {
+            anyhow::bail!("Expected repo '{repo}' to be in the form owner/name")
+        };
+
+        let res: CodeOwnersQueryResult = api.query(CODE_OWNERS, variables!(
+            "owner": owner,
+            "name": name
+        )).await?;
+        // GitHub uses the first CODEOWNERS file it finds in these locations.
+        let text = [res.repository.codeowners, res.repository.root_codeowners, res.repository.docs_codeowners]
+            .into_iter()
+            .flatten()
+            .find_map(|blob| blob.text)
+            .unwrap_or_default();
+        let code_owners = CodeOwners::parse(&text);
+        if !code_owners.has_rules_for(owners) {
+            continue
+        }
+
+        let mut cursor = None;
+        'pages: loop {
+            let res: QueryResult = api.query(&query, variables!(
+                "owner": owner,
+                "name": name,
+                "cursor": cursor
+            )).await?;
+            let repository = res.repository;
+
+            for pr in repository.pull_requests.nodes {
+                // PRs come back most recently updated first, so we can stop once they're too old.
+                if updated_since.is_some_and(|since| pr.updated_at < since) {
+                    break 'pages
+                }
+                // Only the first 100 files that each PR changes are checked, so PRs which touch
+                // our code only after that many other files are missed.
+                if pr.files.nodes.iter().any(|f| code_owners.is_owned_by(&f.path, owners)) {
+                    prs.push(pr.pr.into_issue());
+                }
+            }
+
+            cursor = repository.pull_requests.page_info.end_cursor;
+            if !repository.pull_requests.page_info.has_next_page || cursor.is_none() {
+                break
+            }
+        }
+    }
+
+    find_failing_required_checks(api, &mut prs).await?;
+    Ok(prs)
+}
+
+/// The rules in a CODEOWNERS file.
+struct CodeOwners {
+    rules: Vec<(Regex, Vec<String>)>,
+}
+
+impl CodeOwners {
+    fn parse(text: &str) -> CodeOwners {
+        let rules = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let pattern = codeowners_pattern_to_regex(parts.next()?)?;
+                let owners = parts.map(|o| o.trim_start_matches('@').to_owned()).collect();
+                Some((pattern, owners))
+            })
+            .collect();
+        CodeOwners { rules }
+    }
+
+    /// Do any rules give anything to any of the given owners?
+    fn has_rules_for(&self, owners: &[&str]) -> bool {
+        self.rules.iter().any(|(_, file_owners)| is_any_of(file_owners, owners))
+    }
+
+    /// Is the file at the given path owned by any of the given owners? As in GitHub, the last
+    /// matching rule decides who owns a file.
+    fn is_owned_by(&self, path: &str, owners: &[&str]) -> bool {
+        let Some((_, file_owners)) = self.rules.iter().rev().find(|(pattern, _)| pattern.is_match(path)) else {
+            return false
+        };
+        is_any_of(file_owners, owners)
+    }
+}
+
+fn is_any_of(file_owners: &[String], owners: &[&str]) -> bool {
+    file_owners.iter().any(|o| owners.iter().any(|owner| o.eq_ignore_ascii_case(owner)))
+}
+
+// CODEOWNERS patterns follow (most of) the same rules as .gitignore patterns.
+fn codeowners_pattern_to_regex(pattern: &str) -> Option<Regex> {
+    // Patterns with a slash anywhere but the end only match relative to the repo root.
+    let is_anchored = pattern.trim_end_matches('/').contains('/');
+    let is_dir = pattern.ends_with('/');
+    let pattern = pattern.trim_start_matches('/').trim_end_matches('/');
+    // A wildcard in the last part of the pattern means that it's matching files (or directories)
+    // at that level only, so `docs/*` matches `docs/a.md` but not `docs/a/b.md`.
+    let last_segment = pattern.rsplit('/').next().unwrap_or_default();
+    let has_wildcard = last_segment.contains(['*', '?']);
+
+    let mut regex = String::from(if is_anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            },
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    // Matching a directory matches everything in it.
+    regex.push_str(if is_dir { "/.*$" } else if has_wildcard { "$" } else { "(?:/.*)?$" });
+
+    Regex::new(&regex).ok()
 }
 
-#[derive(Hash, Clone, PartialEq, Eq, serde::Deserialize)]
+// The shape of the PR details that we want to deserialize.
+#[derive(serde::Deserialize)]
+struct QueryNodes<T> {
+    nodes: Vec<T>
+}
+#[derive(serde::Deserialize)]
+struct QueryPr {
+    id: String,
+    draft: bool,
+    review_decision: Option<ReviewDecision>,
+    mergeable: MergeableState,
+    head_commit: QueryNodes<QueryCommit>,
+    latest_reviews: QueryNodes<QueryReview>,
+    review_requests: QueryNodes<QueryReviewRequest>,
+    // Every review ever requested, including those which have since been fulfilled.
+    review_requested_events: QueryNodes<QueryReviewRequest>
+}
+#[derive(serde::Deserialize)]
+struct QueryCommit {
+    commit: QueryCommitDetails
+}
+#[derive(serde::Deserialize)]
+struct QueryCommitDetails {
+    // Null if the commit has no checks or statuses.
+    status_check_rollup: Option<QueryRollup>
+}
+#[derive(serde::Deserialize)]
+struct QueryRollup {
+    state: ChecksState
+}
+#[derive(serde::Deserialize)]
+struct QueryReview {
+    state: ReviewState
+}
+#[derive(serde::Deserialize)]
+struct QueryReviewRequest {
+    requested_reviewer: Option<QueryReviewer>
+}
+#[derive(serde::Deserialize)]
+#[serde(tag = "__typename")]
+enum QueryReviewer {
+    User { login: String },
+    Team { combined_slug: String },
+    // Eg bots and mannequins.
+    #[serde(other)]
+    Other
+}
+
+impl QueryPr {
+    fn requested_review_from_team(&self, team_group_name: &str) -> bool {
+        self.review_requested_events.nodes.iter().any(|r| matches!(
+            &r.requested_reviewer,
+            Some(QueryReviewer::Team { combined_slug }) if combined_slug.eq_ignore_ascii_case(team_group_name)
+        ))
+    }
+
+    fn into_issue(self) -> Issue {
+        Issue {
+            id: self.id,
+            draft: self.draft,
+            review_decision: self.review_decision,
+            mergeable: self.mergeable,
+            checks: self.head_commit.nodes
+                .into_iter()
+                .next()
+                .and_then(|c| c.commit.status_check_rollup)
+                .map(|r| r.state),
+            // Filled in by `find_failing_required_checks`.
+            required_checks_failing: false,
+            latest_reviews: self.latest_reviews.nodes
+                .into_iter()
+                .map(|r| r.state)
+                .collect(),
+            requested_reviewers: self.review_requests.nodes
+                .into_iter()
+                .filter_map(|r| match r.requested_reviewer? {
+                    QueryReviewer::User { login } => Some(login),
+                    QueryReviewer::Team { combined_slug } => Some(combined_slug),
+                    QueryReviewer::Other => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Issue {
     pub id: String,
-    pub draft: bool
-}
\ No newline at end of file
+    pub draft: bool,
+    /// Only given if the repo's branch protection requires reviews.
+    pub review_decision: Option<ReviewDecision>,
+    /// Whether it can be merged without conflicts. GitHub works this out in the
+    /// background, so it's sometimes unknown.
+    pub mergeable: MergeableState,
+    /// The combined state of every check and status on the head commit, if it has any.
+    pub checks: Option<ChecksState>,
+    /// Are any of the checks or statuses that must pass before the PR can be merged failing?
+    pub required_checks_failing: bool,
+    /// The state of the latest review from each reviewer.
+    pub latest_reviews: Vec<ReviewState>,
+    /// Logins of users, and `org/team` slugs of teams, whose review is still requested.
+    pub requested_reviewers: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewDecision {
+    ChangesRequested,
+    Approved,
+    ReviewRequired,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MergeableState {
+    Mergeable,
+    Conflicting,
+    Unknown,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChecksState {
+    Expected,
+    Error,
+    Failure,
+    Pending,
+    Success,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewState {
+    Pending,
+    Commented,
+    Approved,
+    ChangesRequested,
+    Dismissed,
+}
+
+/// Where a PR is in the review process.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    /// Nobody has approved it or asked for changes yet, or somebody's review is still requested.
+    NeedsReview,
+    /// Somebody has asked for changes, so it's waiting on the author.
+    ChangesRequested,
+    /// It's been approved and is ready to merge.
+    Approved,
+}
+
+impl Issue {
+    /// Work out where the PR is in the review process. GitHub only gives us a review decision
+    /// if reviews are required, so otherwise we go by the latest reviews.
+    pub fn review_status(&self) -> ReviewStatus {
+        match self.review_decision {
+            Some(ReviewDecision::ChangesRequested) => return ReviewStatus::ChangesRequested,
+            Some(ReviewDecision::Approved) => return ReviewStatus::Approved,
+            Some(ReviewDecision::ReviewRequired) => return ReviewStatus::NeedsReview,
+            None => {}
+        }
+
+        let has_review = |state| self.latest_reviews.contains(&state);
+        if has_review(ReviewState::ChangesRequested) {
+            ReviewStatus::ChangesRequested
+        } else if has_review(ReviewState::Approved) && self.requested_reviewers.is_empty() {
+            ReviewStatus::Approved
+        } else {
+            ReviewStatus::NeedsReview
+        }
+    }
+
+    /// Which of the given users have been asked to review the PR, and haven't yet.
+    pub fn requested_users<'a>(&self, users: &'a [String]) -> Vec<&'a str> {
+        users
+            .iter()
+            .filter(|user| self.requested_reviewers.iter().any(|r| r.eq_ignore_ascii_case(user)))
+            .map(|user| &**user)
+            .collect()
+    }
+
+    /// Why the PR can't be merged as it is, if there's some reason that we can see.
+    pub fn blocked_reason(&self) -> Option<String> {
+        let mut reasons = Vec::new();
+        if self.mergeable == MergeableState::Conflicting {
+            reasons.push("merge conflicts");
+        }
+        if self.required_checks_failing {
+            reasons.push("failing checks");
+        }
+        (!reasons.is_empty()).then(|| reasons.join(", "))
+    }
+}
+
+/// Work out which of the given PRs have failing checks or statuses that are required to pass
+/// before merging. Only PRs with some failing check or status need to be looked at.
+async fn find_failing_required_checks(api: &Api, prs: &mut [Issue]) -> Result<(), anyhow::Error> {
+    // The shape we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct QueryPr {
+        id: String,
+        head_commit: QueryNodes<QueryCommit>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCommit {
+        commit: QueryCommitDetails
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryCommitDetails {
+        status_check_rollup: Option<QueryRollup>
+    }
+    #[derive(serde::Deserialize)]
+    struct QueryRollup {
+        contexts: QueryNodes<QueryContext>
+    }
+    #[derive(serde::Deserialize)]
+    #[serde(tag = "__typename")]
+    enum QueryContext {
+        CheckRun { conclusion: Option<String>, is_required: bool },
+        StatusContext { state: String, is_required: bool },
+    }
+
+    let is_failing = |context: &QueryContext| match context {
+        // Checks which are still running have no conclusion yet.
+        QueryContext::CheckRun { conclusion, is_required } => *is_required && matches!(
+            conclusion.as_deref(),
+            Some("FAILURE" | "TIMED_OUT" | "CANCELLED" | "ACTION_REQUIRED" | "STARTUP_FAILURE")
+        ),
+        QueryContext::StatusContext { state, is_required } => *is_required && matches!(&**state, "FAILURE" | "ERROR"),
+    };
+
+    let failing_ids: Vec<String> = prs
+        .iter()
+        .filter(|pr| matches!(pr.checks, Some(ChecksState::Failure | ChecksState::Error)))
+        .map(|pr| pr.id.clone())
+        .collect();
+
+    for ids in failing_ids.chunks(PRS_PER_REQUIRED_CHECKS_QUERY) {
+        let mut params = Vec::new();
+        let mut fields = String::new();
+        let mut vars = crate::api::Variables::new();
+        for (n, id) in ids.iter().enumerate() {
+            params.push(format!("$id{n}:ID!"));
+            fields.push_str(&REQUIRED_CHECKS_PR.replace("{n}", &n.to_string()));
+            vars.push(&format!("id{n}"), id);
+        }
+        let query = format!("query RequiredChecks({}) {{{fields}}}", params.join(", "));
+
+        let res: HashMap<String, Option<QueryPr>> = api.query(&query, vars).await?;
+        for pr in res.into_values().flatten() {
+            let failing = pr.head_commit.nodes
+                .iter()
+                .filter_map(|c| c.commit.status_check_rollup.as_ref())
+                .any(|r| r.contexts.nodes.iter().any(is_failing));
+            if let Some(issue) = prs.iter_mut().find(|issue| issue.id == pr.id) {
+                issue.required_checks_failing = failing;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        codeowners_pattern_to_regex(pattern).expect("valid pattern").is_match(path)
+    }
+
+    #[test]
+    fn star_matches_everything() {
+        assert!(matches("*", "README.md"));
+        assert!(matches("*", "src/main.rs"));
+    }
+
+    #[test]
+    fn extension_matches_anywhere() {
+        assert!(matches("*.js", "index.js"));
+        assert!(matches("*.js", "src/app/index.js"));
+        assert!(!matches("*.js", "index.json"));
+        assert!(!matches("*.js", "src/index.ts"));
+    }
+
+    #[test]
+    fn trailing_star_doesnt_match_nested_files() {
+        assert!(matches("docs/*", "docs/index.md"));
+        assert!(!matches("docs/*", "docs/guides/index.md"));
+        assert!(!matches("docs/*", "src/docs/index.md"));
+    }
+
+    #[test]
+    fn anchored_directory_matches_at_root_only() {
+        assert!(matches("/build/", "build/output.txt"));
+        assert!(matches("/build/", "build/logs/output.txt"));
+        assert!(!matches("/build/", "src/build/output.txt"));
+        assert!(!matches("/build/", "build"));
+    }
+
+    #[test]
+    fn unanchored_directory_matches_anywhere() {
+        assert!(matches("apps/", "apps/web/index.js"));
+        assert!(matches("apps/", "src/apps/index.js"));
+        assert!(!matches("apps/", "apps.js"));
+    }
+
+    #[test]
+    fn leading_double_star_matches_in_any_directory() {
+        assert!(matches("**/logs", "logs"));
+        assert!(matches("**/logs", "logs/out.log"));
+        assert!(matches("**/logs", "build/logs/out.log"));
+        assert!(!matches("**/logs", "build/logsfile"));
+    }
+
+    #[test]
+    fn middle_double_star_matches_any_number_of_directories() {
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/b"));
+        assert!(matches("a/**/b", "a/x/y/b/c.rs"));
+        assert!(!matches("a/**/b", "x/a/b"));
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let code_owners = CodeOwners::parse("
+            # Everything belongs to the tools team, apart from the docs.
+            *            @paritytech/subxt-team
+            /docs/       @docs-person
+        ");
+        let team = ["paritytech/subxt-team"];
+        assert!(code_owners.is_owned_by("src/main.rs", &team));
+        assert!(!code_owners.is_owned_by("docs/index.md", &team));
+        assert!(code_owners.is_owned_by("docs/index.md", &["docs-person"]));
+        assert!(code_owners.has_rules_for(&team));
+        assert!(!code_owners.has_rules_for(&["somebody-else"]));
+    }
+
+    #[test]
+    fn rules_with_no_owners_unown_paths() {
+        let code_owners = CodeOwners::parse("
+            *                 @jsdw
+            /generated/
+        ");
+        assert!(code_owners.is_owned_by("src/lib.rs", &["JSDW"]));
+        assert!(!code_owners.is_owned_by("generated/types.rs", &["jsdw"]));
+    }
+}
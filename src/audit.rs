@@ -0,0 +1,293 @@
+use crate::api::{ Api, mutation, common::{ State, FieldValue } };
+use anyhow::Context;
+use std::io::{ BufRead, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+
+/// An append-only JSONL log of every change that we make to issues and project boards,
+/// so that we can see why something changed, and undo it if need be.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    run_id: String,
+    file: Mutex<std::fs::File>,
+}
+
+/// A single line in the audit log.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: time::OffsetDateTime,
+    /// Every entry written during the same sync shares a run ID.
+    pub run_id: String,
+    /// The part of the sync which made the change, eg `sync_milestones`.
+    pub stage: String,
+    /// Why the change was made.
+    pub reason: String,
+    #[serde(flatten)]
+    pub action: AuditAction,
+}
+
+/// The change that was made. Where we know them, previous values are recorded
+/// so that the change can be undone.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AuditAction {
+    /// An issue was created.
+    CreateIssue {
+        repo_id: String,
+        issue_id: String,
+        title: String,
+    },
+    /// An issue was updated. Only the things which changed are given.
+    UpdateIssue {
+        issue_id: String,
+        previous: IssueFields,
+        new: IssueFields,
+    },
+    /// Some content (ie an issue or PR) was added to a project.
+    AddItem {
+        project_id: String,
+        content_id: String,
+        item_id: String,
+    },
+    /// An item was removed from a project. The content ID is missing if the
+    /// content was inaccessible, in which case the item can't be added back.
+    RemoveItem {
+        project_id: String,
+        item_id: String,
+        content_id: Option<String>,
+        previous_values: Vec<PreviousFieldValue>,
+    },
+    /// An item was archived, hiding it from the board.
+    ArchiveItem {
+        project_id: String,
+        item_id: String,
+    },
+    /// An archived item was put back on the board.
+    UnarchiveItem {
+        project_id: String,
+        item_id: String,
+    },
+    /// An item was moved to come straight after another item (or to the top
+    /// of the board if there's no other item).
+    MoveItem {
+        project_id: String,
+        item_id: String,
+        previous_after_id: Option<String>,
+        after_id: Option<String>,
+    },
+    /// A field on a project item was set.
+    SetItemField {
+        project_id: String,
+        item_id: String,
+        field_id: String,
+        previous_value: Option<FieldValue>,
+        value: FieldValue,
+    },
+    /// A field on a project item was cleared.
+    ClearItemField {
+        project_id: String,
+        item_id: String,
+        field_id: String,
+        previous_value: Option<FieldValue>,
+    },
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct IssueFields {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PreviousFieldValue {
+    pub field_id: String,
+    pub value: FieldValue,
+}
+
+impl AuditLog {
+    /// Open the audit log at the given path (creating it if needed). Everything
+    /// recorded will be tagged with the given run ID.
+    pub fn open(path: impl Into<PathBuf>, run_id: String) -> Result<AuditLog, anyhow::Error> {
+        let path = path.into();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+        Ok(AuditLog { path, run_id, file: Mutex::new(file) })
+    }
+
+    /// The ID of the current run.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Make changes via the API on behalf of the given stage, recording each one in this log.
+    pub fn mutations<'a>(&'a self, api: &'a Api, stage: &'a str) -> Mutations<'a> {
+        Mutations { api, audit: self, stage }
+    }
+
+    /// Record a change that we've just made.
+    pub fn record(&self, stage: &str, reason: impl Into<String>, action: AuditAction) -> Result<(), anyhow::Error> {
+        let entry = AuditEntry {
+            timestamp: time::OffsetDateTime::now_utc(),
+            run_id: self.run_id.clone(),
+            stage: stage.to_owned(),
+            reason: reason.into(),
+            action,
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line)
+            .and_then(|_| file.flush())
+            .with_context(|| format!("Failed to write to audit log {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Wraps the mutations in `crate::api::mutation`, recording each change made in the audit log.
+#[derive(Debug, Copy, Clone)]
+pub struct Mutations<'a> {
+    api: &'a Api,
+    audit: &'a AuditLog,
+    stage: &'a str,
+}
+
+impl <'a> Mutations<'a> {
+    /// Create an issue, returning its ID.
+    pub async fn create_issue(&self, reason: &str, repo_id: &str, title: &str, body: &str) -> Result<String, anyhow::Error> {
+        let issue_id = mutation::create_issue::run(self.api, repo_id, title, body).await?;
+        self.audit.record(self.stage, reason, AuditAction::CreateIssue {
+            repo_id: repo_id.to_owned(),
+            issue_id: issue_id.clone(),
+            title: title.to_owned(),
+        })?;
+        Ok(issue_id)
+    }
+
+    /// Update whichever fields are given in `new` on an issue.
+    pub async fn update_issue(&self, reason: &str, issue_id: &str, previous: IssueFields, new: IssueFields) -> Result<(), anyhow::Error> {
+        mutation::update_issue::run(self.api, issue_id, new.title.as_deref(), new.body.as_deref(), new.state).await?;
+        self.audit.record(self.stage, reason, AuditAction::UpdateIssue {
+            issue_id: issue_id.to_owned(),
+            previous,
+            new,
+        })
+    }
+
+    /// Add some content (ie an issue or PR) to a project, returning the item ID.
+    pub async fn add_item(&self, reason: &str, project_id: &str, content_id: &str) -> Result<String, anyhow::Error> {
+        let item_id = mutation::add_item_to_project::run(self.api, content_id, project_id).await?;
+        self.audit.record(self.stage, reason, AuditAction::AddItem {
+            project_id: project_id.to_owned(),
+            content_id: content_id.to_owned(),
+            item_id: item_id.clone(),
+        })?;
+        Ok(item_id)
+    }
+
+    /// Remove an item from a project.
+    pub async fn remove_item(&self, reason: &str, project_id: &str, item_id: &str, content_id: Option<&str>, previous_values: Vec<PreviousFieldValue>) -> Result<(), anyhow::Error> {
+        mutation::remove_item_from_project::run(self.api, project_id, item_id).await?;
+        self.audit.record(self.stage, reason, AuditAction::RemoveItem {
+            project_id: project_id.to_owned(),
+            item_id: item_id.to_owned(),
+            content_id: content_id.map(|id| id.to_owned()),
+            previous_values,
+        })
+    }
+
+    /// Archive an item on a project.
+    pub async fn archive_item(&self, reason: &str, project_id: &str, item_id: &str) -> Result<(), anyhow::Error> {
+        mutation::archive_item_in_project::run(self.api, project_id, item_id).await?;
+        self.audit.record(self.stage, reason, AuditAction::ArchiveItem {
+            project_id: project_id.to_owned(),
+            item_id: item_id.to_owned(),
+        })
+    }
+
+    /// Unarchive an item on a project.
+    pub async fn unarchive_item(&self, reason: &str, project_id: &str, item_id: &str) -> Result<(), anyhow::Error> {
+        mutation::unarchive_item_in_project::run(self.api, project_id, item_id).await?;
+        self.audit.record(self.stage, reason, AuditAction::UnarchiveItem {
+            project_id: project_id.to_owned(),
+            item_id: item_id.to_owned(),
+        })
+    }
+
+    /// Move an item on a project to come straight after another, or to the top.
+    pub async fn move_item(&self, reason: &str, project_id: &str, item_id: &str, previous_after_id: Option<&str>, after_id: Option<&str>) -> Result<(), anyhow::Error> {
+        mutation::update_item_position_in_project::run(self.api, project_id, item_id, after_id).await?;
+        self.audit.record(self.stage, reason, AuditAction::MoveItem {
+            project_id: project_id.to_owned(),
+            item_id: item_id.to_owned(),
+            previous_after_id: previous_after_id.map(|id| id.to_owned()),
+            after_id: after_id.map(|id| id.to_owned()),
+        })
+    }
+
+    /// Set a field on a project item.
+    pub async fn set_item_field(&self, reason: &str, project_id: &str, item_id: &str, field_id: &str, previous_value: Option<FieldValue>, value: FieldValue) -> Result<(), anyhow::Error> {
+        mutation::update_item_field_in_project::run(self.api, project_id, item_id, field_id, &value).await?;
+        self.audit.record(self.stage, reason, AuditAction::SetItemField {
+            project_id: project_id.to_owned(),
+            item_id: item_id.to_owned(),
+            field_id: field_id.to_owned(),
+            previous_value,
+            value,
+        })
+    }
+
+    /// Set a single select field on a project item, given option IDs.
+    pub async fn set_item_option(&self, reason: &str, project_id: &str, item_id: &str, field_id: &str, previous_option_id: Option<&str>, option_id: &str) -> Result<(), anyhow::Error> {
+        let previous_value = previous_option_id.map(|id| FieldValue::SingleSelect(id.to_owned()));
+        let value = FieldValue::SingleSelect(option_id.to_owned());
+        self.set_item_field(reason, project_id, item_id, field_id, previous_value, value).await
+    }
+
+    /// Clear a field on a project item.
+    pub async fn clear_item_field(&self, reason: &str, project_id: &str, item_id: &str, field_id: &str, previous_value: Option<FieldValue>) -> Result<(), anyhow::Error> {
+        mutation::clear_item_field_in_project::run(self.api, project_id, item_id, field_id).await?;
+        self.audit.record(self.stage, reason, AuditAction::ClearItemField {
+            project_id: project_id.to_owned(),
+            item_id: item_id.to_owned(),
+            field_id: field_id.to_owned(),
+            previous_value,
+        })
+    }
+}
+
+/// Read every entry from the audit log at the given path, oldest first.
+pub fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, anyhow::Error> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+
+    let mut entries = Vec::new();
+    for (idx, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue
+        }
+        let entry = serde_json::from_str(&line)
+            .with_context(|| format!("Failed to decode line {} of audit log {}", idx + 1, path.display()))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Generate a new run ID. These are just the current time, which is unique enough
+/// given that syncs don't overlap, and makes it easy to find the run you're after.
+pub fn new_run_id() -> String {
+    let format = time::format_description::parse("[year][month][day]T[hour][minute][second].[subsecond digits:3]Z")
+        .expect("should be valid date format");
+    time::OffsetDateTime::now_utc().format(&format).expect("date should format properly")
+}
@@ -0,0 +1,325 @@
+use crate::api::query::team::RepoPermission;
+use crate::rules::{ Exclusions, Retention, Rule, Source };
+use anyhow::Context;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Configuration which can be given in a TOML file. Anything that isn't
+/// given falls back to the defaults below.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The orgs to look for issues and PRs in. Repos given as just `name` (rather
+    /// than `owner/name`) anywhere in the config are in the first of these.
+    #[serde(default = "default_orgs")]
+    pub orgs: Vec<String>,
+    /// The rules deciding which issues and PRs go in which project columns.
+    #[serde(default = "default_rules", rename = "rule")]
+    pub rules: Vec<Rule>,
+    /// Issues and PRs that no rule should put on the board.
+    #[serde(default)]
+    pub exclude: Exclusions,
+    /// Remove items whose issue or PR has been deleted, or moved somewhere that we
+    /// can't see, from the boards that rules target. These are always logged.
+    #[serde(default)]
+    pub remove_inaccessible_items: bool,
+    /// Repos (as `name` in our default org, or `owner/name`) whose CODEOWNERS files are checked for
+    /// code owned by the team group or team members. Open PRs touching that code are
+    /// included in the `team_prs` source.
+    #[serde(default)]
+    pub code_owner_repos: Vec<String>,
+    /// Fields on managed items which are kept in sync with their labels.
+    #[serde(default, rename = "label_field")]
+    pub label_fields: Vec<LabelField>,
+    /// Settings for particular status columns, keyed by status name.
+    #[serde(default, rename = "column")]
+    pub columns: HashMap<String, ColumnConfig>,
+    /// Who is in the team, and which repos belong to it.
+    #[serde(default)]
+    pub team: TeamConfig,
+}
+
+/// Where the team members and team repos come from. By default, these are the built in
+/// lists, but they can instead be looked up from the team group on GitHub at the start of
+/// each sync. Either way, the include and exclude lists are then applied.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamConfig {
+    /// Use the members of the team group rather than the built in list of members.
+    #[serde(default)]
+    pub discover_members: bool,
+    /// Use the (unarchived) repos in our orgs that the team group has access to, rather
+    /// than the built in list of repos.
+    #[serde(default)]
+    pub discover_repos: bool,
+    /// Only discover repos that the team group has at least this permission on.
+    #[serde(default)]
+    pub repo_permission: Option<RepoPermission>,
+    /// Only discover repos with at least one of these topics.
+    #[serde(default)]
+    pub repo_topics: Vec<String>,
+    /// Members to add to the team, on top of the built in or discovered ones.
+    #[serde(default)]
+    pub include_members: Vec<String>,
+    /// Members to leave out of the team.
+    #[serde(default)]
+    pub exclude_members: Vec<String>,
+    /// Repos (as `name` in our default org, or `owner/name`) to add to the team repos.
+    #[serde(default)]
+    pub include_repos: Vec<String>,
+    /// Repos (as `name` in our default org, or `owner/name`) to leave out of the team repos.
+    #[serde(default)]
+    pub exclude_repos: Vec<String>,
+}
+
+/// A single select field whose value comes from the labels on each item.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelField {
+    /// The name of the field.
+    pub field: String,
+    /// Labels in order of precedence; the field is set from the first of these that an
+    /// item has, and cleared if it has none of them.
+    pub labels: Vec<String>,
+    /// The option to choose for each label, if it's not named the same as the label.
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+}
+
+impl LabelField {
+    /// The name of the option to choose for an item with the given labels, if any.
+    pub fn option_for(&self, labels: &[String]) -> Option<&str> {
+        let label = self.labels.iter().find(|label| labels.iter().any(|l| l.eq_ignore_ascii_case(label)))?;
+        let option = self.options
+            .iter()
+            .find(|(l, _)| l.eq_ignore_ascii_case(label))
+            .map_or(label, |(_, option)| option);
+        Some(option)
+    }
+}
+
+/// Settings for a status column.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnConfig {
+    /// What to do with items that no longer belong in this column.
+    #[serde(default)]
+    pub removal: RemovalMode,
+    /// How to order the items in this column, most significant first. Items which
+    /// tie on every key are left in whatever order they're already in.
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+    /// Labels in order of priority, for the `label_priority` sort key.
+    #[serde(default)]
+    pub label_priority: Vec<String>,
+    /// When to consider items in this column stale, and what to do about it.
+    #[serde(default)]
+    pub stale: Option<StaleConfig>,
+}
+
+/// Items whose issue or PR hasn't been updated, commented on or committed to for a while
+/// are stale. Stale items are always logged.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaleConfig {
+    /// How many days without activity before an item is stale.
+    pub days: i64,
+    /// Different thresholds for items with particular labels. If an item has several of
+    /// these labels, the shortest threshold applies.
+    #[serde(default)]
+    pub labels: HashMap<String, i64>,
+    /// A text or date field to set to the date of the last activity on stale items.
+    /// It's cleared on items which aren't stale.
+    #[serde(default)]
+    pub field: Option<String>,
+    /// A status column to move stale items to. They move back once there's some activity.
+    #[serde(default)]
+    pub move_to: Option<String>,
+}
+
+impl StaleConfig {
+    /// How many days an item with the given labels can go without activity.
+    pub fn days_for(&self, labels: &[String]) -> i64 {
+        self.labels
+            .iter()
+            .filter(|(label, _)| labels.iter().any(|l| l.eq_ignore_ascii_case(label)))
+            .map(|(_, days)| *days)
+            .min()
+            .unwrap_or(self.days)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Oldest issues and PRs (by creation date) first.
+    OldestFirst,
+    /// Things in the milestones which are due soonest first, and things with no due milestone last.
+    MilestoneDueDate,
+    /// Things with the highest priority label (see `label_priority`) first, and things with none last.
+    LabelPriority,
+    /// Most recently updated first.
+    LastUpdated,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalMode {
+    /// Delete the item from the project.
+    #[default]
+    Delete,
+    /// Archive the item, so that it (and its field values) can still be seen in the
+    /// project's archive. It's unarchived if it belongs on the board again.
+    Archive,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            orgs: default_orgs(),
+            rules: default_rules(),
+            exclude: Exclusions::default(),
+            remove_inaccessible_items: false,
+            code_owner_repos: Vec::new(),
+            label_fields: Vec::new(),
+            columns: HashMap::new(),
+            team: TeamConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load config from the given TOML file, or use the defaults if no file is given.
+    pub fn load(path: Option<&Path>) -> Result<Config, anyhow::Error> {
+        let Some(path) = path else {
+            return Ok(Config::default())
+        };
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to decode config file {}", path.display()))
+    }
+}
+
+impl Config {
+    /// Settings for the given status column, if any are given.
+    pub fn column(&self, status: &str) -> Option<&ColumnConfig> {
+        self.columns
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(status))
+            .map(|(_, column)| column)
+    }
+
+    /// How items should be removed from the given status column.
+    pub fn removal_mode(&self, status: &str) -> RemovalMode {
+        self.column(status)
+            .map(|column| column.removal)
+            .unwrap_or_default()
+    }
+}
+
+/// The orgs we look in if none are configured.
+fn default_orgs() -> Vec<String> {
+    crate::ORGS.iter().map(|org| org.to_string()).collect()
+}
+
+/// The rules we use if none are configured.
+fn default_rules() -> Vec<Rule> {
+    let rule = |name: &str, source: Source, status: &str| Rule {
+        name: name.to_owned(),
+        source,
+        project: None,
+        status: status.to_owned(),
+        priority: 0,
+        fields: HashMap::new(),
+        exclude: Exclusions::default(),
+    };
+
+    vec![
+        rule("assigned issues", Source::AssignedIssues { pr_in_review: None }, crate::ASSIGNED_ISSUE_STATUS_NAME),
+        rule("draft prs", Source::TeamPrs { draft: Some(true), review: None, blocked: None }, crate::DRAFT_PR_STATUS_NAME),
+        rule("prs needing review", Source::TeamPrs { draft: Some(false), review: None, blocked: None }, crate::NEEDS_REVIEW_STATUS_NAME),
+        rule("closed issues", Source::ClosedIssues { retention: Retention::default() }, crate::FINISHED_ISSUE_STATUS_NAME),
+        rule("merged prs", Source::MergedPrs { retention: Retention::default(), include_reviewed: false, include_coauthored: false }, crate::FINISHED_PR_STATUS_NAME),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label_field(labels: &[&str], options: &[(&str, &str)]) -> LabelField {
+        LabelField {
+            field: "Priority".to_owned(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            options: options.iter().map(|(l, o)| (l.to_string(), o.to_string())).collect(),
+        }
+    }
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn config_is_parsed() {
+        let config: Config = toml::from_str(r#"
+            orgs = ["paritytech", "other-org"]
+
+            [[rule]]
+            name = "prs to review"
+            source = { kind = "team_prs", draft = false }
+            status = "needs review"
+            priority = 1
+            fields = { Priority = "High" }
+
+            [column."needs review"]
+            removal = "archive"
+            sort = ["oldest_first"]
+        "#).expect("valid config");
+        assert_eq!(config.orgs, vec!["paritytech", "other-org"]);
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.removal_mode("Needs Review"), RemovalMode::Archive);
+        assert_eq!(config.removal_mode("done"), RemovalMode::Delete);
+    }
+
+    #[test]
+    fn unknown_config_fields_are_rejected() {
+        let parse = |toml: &str| toml::from_str::<Config>(toml);
+        assert!(parse(r#"org = ["paritytech"]"#).is_err());
+        assert!(parse(r#"
+            [[rule]]
+            name = "assigned"
+            source = { kind = "assigned_issues" }
+            status = "todo"
+            project_number = 1
+        "#).is_err());
+        assert!(parse(r#"
+            [column.todo]
+            removal_mode = "archive"
+        "#).is_err());
+        assert!(parse(r#"
+            [team]
+            discover_member = true
+        "#).is_err());
+        assert!(parse(r#"
+            [exclude]
+            label = ["wontfix"]
+        "#).is_err());
+    }
+
+    #[test]
+    fn option_for_uses_the_first_matching_label() {
+        let field = label_field(&["P0", "P1", "P2"], &[]);
+        assert_eq!(field.option_for(&labels(&["P2", "bug", "P1"])), Some("P1"));
+        assert_eq!(field.option_for(&labels(&["p0"])), Some("P0"));
+        assert_eq!(field.option_for(&labels(&["bug"])), None);
+        assert_eq!(field.option_for(&[]), None);
+    }
+
+    #[test]
+    fn option_for_maps_labels_to_options() {
+        let field = label_field(&["I1-urgent", "I2-soon"], &[("i1-urgent", "Urgent")]);
+        assert_eq!(field.option_for(&labels(&["I1-urgent"])), Some("Urgent"));
+        assert_eq!(field.option_for(&labels(&["I2-soon"])), Some("I2-soon"));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::api::{ Api, query, mutation };
+use anyhow::Context;
+use std::io::Write;
+use std::path::Path;
+use tracing::{ info, warn };
+
+/// The title given to draft issues on the project board which act as leases. These
+/// aren't returned when listing project items, so that syncs leave them alone.
+pub const LEASE_TITLE: &str = "🔒 project sync lease (do not edit)";
+
+/// Held for the duration of a run, to stop runs from overlapping.
+#[derive(Debug)]
+pub struct RunLock {
+    // The OS releases this when the file is closed (including if the process dies).
+    _file: std::fs::File,
+    lease: Option<tokio::sync::Mutex<BoardLease>>,
+}
+
+#[derive(Debug)]
+struct BoardLease {
+    org: String,
+    project_number: usize,
+    ttl: time::Duration,
+    project_id: String,
+    item_id: String,
+    /// When GitHub says the lease item was created; it expires `ttl` after this. Other
+    /// runs judge whether it's live by the same timestamp.
+    created_at: time::OffsetDateTime,
+}
+
+/// Options for taking a lease on a project board, in addition to the file lock.
+#[derive(Debug, Copy, Clone)]
+pub struct BoardLeaseOpts<'a> {
+    pub org: &'a str,
+    pub project_number: usize,
+    /// Leases older than this are assumed to have been left behind by a run that died.
+    pub ttl: time::Duration,
+}
+
+impl RunLock {
+    /// Take an exclusive lock on the given file, and optionally a lease on a project
+    /// board too (for when runs may happen on different machines). Fails if some other
+    /// run is already holding either.
+    pub async fn acquire(api: &Api, lock_file: &Path, board_lease: Option<BoardLeaseOpts<'_>>) -> Result<RunLock, anyhow::Error> {
+        let file = acquire_file_lock(lock_file)?;
+        let lease = match board_lease {
+            Some(opts) => Some(tokio::sync::Mutex::new(acquire_board_lease(api, opts).await?)),
+            None => None,
+        };
+        Ok(RunLock { _file: file, lease })
+    }
+
+    /// Make sure that we still hold the lock, renewing the board lease once it's half way to
+    /// expiring. Long runs should call this every so often, and especially before making changes,
+    /// so that they give up rather than carry on alongside a run that thinks our lease expired.
+    /// This only talks to GitHub when the lease needs renewing.
+    pub async fn renew(&self, api: &Api) -> Result<(), anyhow::Error> {
+        let Some(lease) = &self.lease else {
+            return Ok(())
+        };
+        let mut lease = lease.lock().await;
+        let project_number = lease.project_number;
+
+        let now = time::OffsetDateTime::now_utc();
+        let age = now - lease.created_at;
+        if age >= lease.ttl {
+            anyhow::bail!("Our lease on project {project_number} expired before the sync finished, so another sync may have started");
+        }
+        if age < lease.ttl / 2 {
+            return Ok(())
+        }
+
+        // If our lease has gone, somebody else must have removed it.
+        let current = query::project_leases::run(api, &lease.org, project_number, LEASE_TITLE).await?;
+        if !current.leases.iter().any(|l| l.item_id == lease.item_id) {
+            anyhow::bail!("Our lease on project {project_number} was removed, so another sync may have started");
+        }
+
+        // Leases expire based on when they were created, so replace ours with a new one. The new
+        // one is added before the old one is removed so that there's always a live lease.
+        let body = lease_body(now);
+        let new_lease = mutation::add_draft_issue_to_project::run(api, &lease.project_id, LEASE_TITLE, &body).await?;
+        let old_item_id = std::mem::replace(&mut lease.item_id, new_lease.item_id);
+        lease.created_at = new_lease.created_at;
+        mutation::remove_item_from_project::run(api, &lease.project_id, &old_item_id).await?;
+
+        info!("🔒 renewed lease on project {project_number}");
+        Ok(())
+    }
+
+    /// Release the lock. The file lock is released regardless, but the board lease
+    /// will be left to expire if we can't remove it.
+    pub async fn release(self, api: &Api) {
+        if let Some(lease) = self.lease {
+            let lease = lease.into_inner();
+            if let Err(e) = mutation::remove_item_from_project::run(api, &lease.project_id, &lease.item_id).await {
+                warn!("🛑 failed to remove lease from project board (it'll expire eventually): {e:?}");
+            }
+        }
+    }
+}
+
+fn acquire_file_lock(path: &Path) -> Result<std::fs::File, anyhow::Error> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => {},
+        Err(std::fs::TryLockError::WouldBlock) => {
+            anyhow::bail!("Another sync is already running (lock file {} is held)", path.display())
+        },
+        Err(std::fs::TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+
+    // Note who holds the lock, to help with debugging.
+    let holder = format!("pid {} since {}\n", std::process::id(), time::OffsetDateTime::now_utc());
+    file.set_len(0)?;
+    file.write_all(holder.as_bytes())?;
+
+    Ok(file)
+}
+
+async fn acquire_board_lease(api: &Api, opts: BoardLeaseOpts<'_>) -> Result<BoardLease, anyhow::Error> {
+    let BoardLeaseOpts { org, project_number, ttl } = opts;
+    let now = time::OffsetDateTime::now_utc();
+    let is_live = |lease: &query::project_leases::Lease| now - lease.created_at < ttl;
+
+    // Back off right away if somebody else holds a live lease.
+    let existing = query::project_leases::run(api, org, project_number, LEASE_TITLE).await?;
+    if existing.leases.iter().any(is_live) {
+        anyhow::bail!("Another sync is already running (a lease is held on project {project_number})");
+    }
+
+    // Create our own lease. Somebody else may have done the same in the meantime, so we then
+    // look again and only proceed if ours is the oldest live lease. Leases are just housekeeping,
+    // so unlike other changes to the board they aren't recorded in the audit log.
+    let body = lease_body(now);
+    let new_lease = mutation::add_draft_issue_to_project::run(api, &existing.project_id, LEASE_TITLE, &body).await?;
+    let lease = BoardLease {
+        org: org.to_owned(),
+        project_number,
+        ttl,
+        project_id: existing.project_id,
+        item_id: new_lease.item_id,
+        created_at: new_lease.created_at,
+    };
+
+    let current = query::project_leases::run(api, org, project_number, LEASE_TITLE).await?;
+    let oldest_live_lease = current.leases
+        .iter()
+        .filter(|l| is_live(l) || l.item_id == lease.item_id)
+        .min_by(|a, b| (a.created_at, &a.item_id).cmp(&(b.created_at, &b.item_id)));
+
+    if oldest_live_lease.is_some_and(|l| l.item_id != lease.item_id) {
+        mutation::remove_item_from_project::run(api, &lease.project_id, &lease.item_id).await?;
+        anyhow::bail!("Another sync is already running (a lease is held on project {project_number})");
+    }
+
+    // We hold the lease, so tidy away any expired ones left behind by runs that died.
+    for expired in current.leases.iter().filter(|l| !is_live(l) && l.item_id != lease.item_id) {
+        info!("🧹 removing expired lease from project board");
+        mutation::remove_item_from_project::run(api, &lease.project_id, &expired.item_id).await?;
+    }
+
+    Ok(lease)
+}
+
+fn lease_body(now: time::OffsetDateTime) -> String {
+    format!("Held by pid {} since {now}. This will be removed when the sync finishes.", std::process::id())
+}
@@ -1,23 +1,41 @@
 mod api;
 mod utils;
 mod sync_milestones;
-mod sync_assigned_issues;
-mod sync_closed_things;
-mod sync_draft_prs;
-mod sync_prs_needing_review;
+mod sync_rules;
+mod sort_columns;
+mod rules;
+mod config;
+mod serve;
+mod state_store;
+mod audit;
+mod undo;
+mod lock;
 
 use api::Api;
+use clap::Parser;
+use utils::SyncScope;
+use state_store::StateStore;
+use audit::AuditLog;
+use lock::{ RunLock, BoardLeaseOpts };
+use std::path::PathBuf;
+use tracing::info;
 use sync_milestones::{ sync_milestones, SyncMilestoneOpts };
-use sync_assigned_issues::{ sync_assigned_issues, SyncAssignedIssuesOpts };
-use sync_closed_things::{ sync_closed_things, SyncClosedThingOpts };
-use sync_draft_prs::{ sync_draft_prs, SyncDraftPrOpts };
-use sync_prs_needing_review::{ sync_prs_needing_review, SyncPrsNeedingReviewOpts };
+use sync_rules::{ sync_rules, SyncRulesOpts };
+use config::Config;
 
-// The organisation to search for projects, repos and issues in.
-const ORG: &str = "paritytech";
+// The organisation that our projects, and the repo that we create
+// milestone issues in, live in.
+const PROJECT_ORG: &str = "paritytech";
 
-// The rpositories within the above organisation that we will
-// sync milestones from.
+// The organisations to search for issues and PRs in, unless the config
+// says otherwise. Repos given as just a name are in the first of these.
+const ORGS: &[&str] = &[
+    "paritytech",
+];
+
+// The rpositories (as `name` in the first of the above organisations, or
+// `owner/name`) that we will sync milestones from, unless the config says
+// to discover them from the team group instead.
 const REPO_NAMES: &[&str] = &[
     "subxt",
     "jsonrpsee",
@@ -33,7 +51,8 @@ const REPO_NAMES: &[&str] = &[
 ];
 
 // Team members that we'll search for assigned issues for to
-// sync those to our local project board.
+// sync those to our local project board, unless the config says
+// to discover them from the team group instead.
 const TEAM_MEMBERS: &[&str] = &[
     "jsdw",
     "niklasad1",
@@ -41,7 +60,7 @@ const TEAM_MEMBERS: &[&str] = &[
     "pkhry",
 ];
 
-// The repository within the organisation above to use to create
+// The repository within the project organisation to use to create
 // issues in whose sole purpose is to be kept in sync with milestones
 // and be something that can be added to project boards.
 const PROJECT_REPO_NAME: &str = "subxt-team-milestones";
@@ -52,7 +71,13 @@ const LOCAL_PROJECT_NUMBER: usize = 22;
 
 // Statuses to look for in the local project to sync lists of milestones,
 // issues assigned to team members, and PRs needing review from the team.
+// Apart from the milestone status, these are only used by the default rules
+// (see `config.rs`), which a config file can replace.
 const MILESTONE_STATUS_NAME: &str = "milestone";
+
+// If the local project has a date field with this name, milestone items
+// on it will have it set to the milestone due date.
+const MILESTONE_DUE_DATE_FIELD_NAME: &str = "Due date";
 const ASSIGNED_ISSUE_STATUS_NAME: &str = "in progress";
 const DRAFT_PR_STATUS_NAME: &str = "draft prs";
 const NEEDS_REVIEW_STATUS_NAME: &str = "needs review";
@@ -73,6 +98,91 @@ const PUBLIC_ROADMAP_PROJECT_NUMBER: usize = 27;
 // The team name to set on public roadmap issues in the "team" single select field.
 const ROADMAP_TEAM_NAME: &str = "Subxt";
 
+// Incremental syncs look for things updated a little before the last successful sync
+// started, so that we don't miss anything that GitHub's search index was slow to pick up.
+// For the same reason, they don't remove things updated less than this long ago.
+const INCREMENTAL_SYNC_OVERLAP: time::Duration = time::Duration::minutes(10);
+
+#[derive(Debug, clap::Parser)]
+#[command(about = "Sync milestones, issues and PRs to Github project boards")]
+struct Opts {
+    #[command(flatten)]
+    sync_opts: SyncOpts,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Options which apply to every sync.
+#[derive(Debug, Clone, clap::Args)]
+pub struct SyncOpts {
+    /// A TOML file configuring the rules that decide which issues and PRs go where. This is
+    /// re-read on every sync. If not given, the built in defaults are used.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// A JSON file to persist state in between runs. It's created if it doesn't exist.
+    #[arg(long, global = true, default_value = "project-sync-state.json")]
+    state_file: PathBuf,
+    /// Normally we only look at things updated since the last successful sync. Every this
+    /// many hours, we instead check everything to fix anything that's drifted out of sync.
+    #[arg(long, global = true, default_value_t = 24)]
+    full_reconcile_interval_hours: i64,
+    /// Check everything on this run, rather than only things updated since the last one.
+    #[arg(long, global = true)]
+    full_reconcile: bool,
+    /// A JSONL file that every change we make is appended to.
+    #[arg(long, global = true, default_value = "project-sync-audit.jsonl")]
+    audit_log: PathBuf,
+    /// A file which is locked for the duration of each run, so that runs on this machine don't overlap.
+    #[arg(long, global = true, default_value = "project-sync.lock")]
+    lock_file: PathBuf,
+    /// Also hold a lease on the local project board for the duration of each run, so that
+    /// runs on different machines don't overlap.
+    #[arg(long, global = true)]
+    board_lease: bool,
+    /// Board leases older than this many minutes are assumed to be left over from a run that
+    /// died, and are ignored.
+    #[arg(long, global = true, default_value_t = 60)]
+    board_lease_ttl_mins: i64,
+}
+
+impl SyncOpts {
+    /// Acquire the lock(s) which must be held while syncing.
+    async fn acquire_lock(&self, api: &Api) -> Result<RunLock, anyhow::Error> {
+        let board_lease = self.board_lease.then(|| BoardLeaseOpts {
+            org: PROJECT_ORG,
+            project_number: LOCAL_PROJECT_NUMBER,
+            ttl: time::Duration::minutes(self.board_lease_ttl_mins),
+        });
+        RunLock::acquire(api, &self.lock_file, board_lease).await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Run a single full sync and then exit. This is the default.
+    Sync,
+    /// Run an HTTP server which syncs things in response to GitHub webhook
+    /// deliveries, as well as running a periodic full sync.
+    Serve(serve::ServeOpts),
+    /// Undo every change made during some previous run, as recorded in the audit log.
+    Undo {
+        /// The ID of the run to undo.
+        #[arg(long)]
+        run: String,
+    },
+}
+
+/// What a call to `sync` should cover.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncTarget {
+    /// Every milestone and every project board column.
+    Everything,
+    /// A single milestone in one of our repos (given as `owner/name`).
+    Milestone { repo: String, number: usize },
+    /// Some issues and PRs, given their node IDs.
+    Items(Vec<String>),
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     // Init the logging.
@@ -80,8 +190,7 @@ async fn main() -> Result<(), anyhow::Error> {
         .with_span_events(tracing_subscriber::fmt::format::FmtSpan::ENTER)
         .init();
 
-    let repo_names: Vec<String> = REPO_NAMES.into_iter().map(|r| r.to_string()).collect();
-    let team_members: Vec<String> = TEAM_MEMBERS.into_iter().map(|r| r.to_string()).collect();
+    let opts = Opts::parse();
 
     // Get the access token:
     let token = match std::env::var("GITHUB_TOKEN") {
@@ -92,66 +201,233 @@ async fn main() -> Result<(), anyhow::Error> {
     // Spin up an API client to talk to github.
     let api = Api::new(token);
 
+    match opts.command.unwrap_or(Command::Sync) {
+        Command::Sync => sync(&api, &opts.sync_opts, &SyncTarget::Everything).await,
+        Command::Serve(serve_opts) => serve::run(api, opts.sync_opts, serve_opts).await,
+        Command::Undo { run } => {
+            let lock = opts.sync_opts.acquire_lock(&api).await?;
+            let audit = AuditLog::open(&opts.sync_opts.audit_log, audit::new_run_id())?;
+            info!("↩️  undo run ID is {}", audit.run_id());
+            let res = undo::undo_run(&api, &audit, &opts.sync_opts.audit_log, &run).await;
+            lock.release(&api).await;
+            res
+        },
+    }
+}
+
+/// Sync the given target to the project boards.
+pub async fn sync(api: &Api, opts: &SyncOpts, target: &SyncTarget) -> Result<(), anyhow::Error> {
+    let lock = opts.acquire_lock(api).await?;
+    let res = sync_with_lock(api, opts, target, &lock).await;
+    lock.release(api).await;
+    res
+}
+
+async fn sync_with_lock(api: &Api, opts: &SyncOpts, target: &SyncTarget, lock: &RunLock) -> Result<(), anyhow::Error> {
+    let config = Config::load(opts.config.as_deref())?;
+    let mut state = StateStore::load(&opts.state_file)?;
+    let started_at = time::OffsetDateTime::now_utc();
+    let audit = AuditLog::open(&opts.audit_log, audit::new_run_id())?;
+    info!("🔄 sync run ID is {}", audit.run_id());
+
+    // When syncing everything, we only look at things updated since the last successful
+    // sync, unless it's time for a full reconcile.
+    let last_sync = state.state.last_successful_sync;
+    let is_full_reconcile_due = opts.full_reconcile || match state.state.last_full_reconcile {
+        Some(last_full_reconcile) => started_at - last_full_reconcile >= time::Duration::hours(opts.full_reconcile_interval_hours),
+        None => true
+    };
+    let updated_since = match (target, last_sync) {
+        (SyncTarget::Everything, Some(last_sync)) if !is_full_reconcile_due => Some(last_sync - INCREMENTAL_SYNC_OVERLAP),
+        _ => None
+    };
+    if let SyncTarget::Everything = target {
+        match updated_since {
+            Some(since) => info!("🔄 syncing things updated since {since}"),
+            None => info!("🔄 syncing everything (full reconcile)"),
+        }
+    }
+
+    let res = sync_stages(api, &config, target, updated_since, &mut state, &audit, lock).await;
+
+    if res.is_ok() && matches!(target, SyncTarget::Everything) {
+        state.state.last_successful_sync = Some(started_at);
+        if updated_since.is_none() {
+            state.state.last_full_reconcile = Some(started_at);
+        }
+    }
+
+    // Save whatever progress we made, even if something went wrong part way through.
+    state.save()?;
+    res
+}
+
+/// Run the sync stages needed to sync some target. If `updated_since` is given, only
+/// things updated since then are synced. The lock is renewed between stages.
+async fn sync_stages(api: &Api, config: &Config, target: &SyncTarget, updated_since: Option<time::OffsetDateTime>, state: &mut StateStore, audit: &AuditLog, lock: &RunLock) -> Result<(), anyhow::Error> {
+    if config.orgs.is_empty() {
+        anyhow::bail!("At least one org must be configured to look for issues and PRs in")
+    }
+    let Team { members: team_members, repos: repo_names } = team(api, &config.orgs, &config.team).await?;
+    let local_issue_repo = format!("{PROJECT_ORG}/{PROJECT_REPO_NAME}");
+
+    // Webhook deliveries can point at milestones in any repo, so check that this is one of
+    // ours before doing anything else.
+    if let SyncTarget::Milestone { repo, .. } = target {
+        if !repo_names.iter().any(|r| r.eq_ignore_ascii_case(repo)) {
+            info!("ignoring milestone in {repo}, which isn't a team repo");
+            return Ok(())
+        }
+    }
+
     // Project details used by a few places:
     let project_details = api::query::project_details::run(
-        &api,
-        ORG,
+        api,
+        PROJECT_ORG,
         LOCAL_PROJECT_NUMBER,
         PUBLIC_ROADMAP_PROJECT_NUMBER
     ).await?;
 
+    // Work out which stages to run, and which items they are allowed to touch.
+    let (milestone_repos, only_milestone_number) = match target {
+        SyncTarget::Everything => (repo_names.clone(), None),
+        SyncTarget::Milestone { repo, number } => (vec![repo.clone()], Some(*number)),
+        SyncTarget::Items(_) => (Vec::new(), None),
+    };
+    let do_sync_rules = matches!(target, SyncTarget::Everything | SyncTarget::Items(_));
+    // When syncing particular issues and PRs, we only need to look for things updated since they
+    // were. Opening, closing or marking a PR as ready for review doesn't count as updating the
+    // issues that it will close, but where those belong can depend on it, so they're synced too.
+    let mut item_ids = Vec::new();
+    let mut items_updated_since = None;
+    if let SyncTarget::Items(ids) = target {
+        let ids: Vec<&str> = ids.iter().map(|id| &**id).collect();
+        let summaries = api::query::content_summaries::run(api, &ids).await?;
+        for (id, summary) in &summaries {
+            item_ids.push(id.clone());
+            item_ids.extend(summary.closing_issue_ids.iter().cloned());
+        }
+        items_updated_since = summaries.values().map(|s| s.updated_at).min();
+        if items_updated_since.is_none() {
+            info!("none of the issues and PRs to sync could be found");
+            return Ok(())
+        }
+    }
+    let scope = match (target, items_updated_since) {
+        (SyncTarget::Items(_), Some(updated_since)) => SyncScope::Content { ids: &item_ids, updated_since },
+        _ => match updated_since {
+            Some(since) => SyncScope::UpdatedSince {
+                since,
+                settled_before: time::OffsetDateTime::now_utc() - INCREMENTAL_SYNC_OVERLAP
+            },
+            None => SyncScope::All,
+        },
+    };
+
     // Sync milestones to project boards.
-    sync_milestones(SyncMilestoneOpts {
-        api: &api,
-        project_details: &project_details,
-        local_issue_repo_name: PROJECT_REPO_NAME,
-        local_project_milestone_status: MILESTONE_STATUS_NAME,
-        org: ORG,
-        repos_to_sync: &repo_names,
-        roadmap_team_name: ROADMAP_TEAM_NAME,
-    }).await?;
-
-    // Sync assigned issues:
-    sync_assigned_issues(SyncAssignedIssuesOpts {
-        api: &api,
-        local_issue_repo_name: PROJECT_REPO_NAME,
-        project_details: &project_details.tools,
-        field_status_value_name: ASSIGNED_ISSUE_STATUS_NAME,
-        team_members: &team_members,
-        org: ORG,
-    }).await?;
-
-    // Sync draft PRs:
-    sync_draft_prs(SyncDraftPrOpts {
-        api: &api,
-        project_details: &project_details.tools,
-        field_status_value_name: DRAFT_PR_STATUS_NAME,
-        team_group_name: TOOLS_TEAM_GROUP,
-        team_members: &team_members,
-        team_repos: &repo_names,
-        org: ORG,
-    }).await?;
-
-    // Sync PRs needing review:
-    sync_prs_needing_review(SyncPrsNeedingReviewOpts {
-        api: &api,
-        project_details: &project_details.tools,
-        field_status_value_name: NEEDS_REVIEW_STATUS_NAME,
-        team_group_name: TOOLS_TEAM_GROUP,
-        team_members: &team_members,
-        team_repos: &repo_names,
-        org: ORG,
-    }).await?;
-
-    // Sync closed issues and PRs:
-    sync_closed_things(SyncClosedThingOpts {
-        api: &api,
-        project_details: &project_details.tools,
-        closed_pr_status_name: FINISHED_PR_STATUS_NAME,
-        closed_issue_status_name: FINISHED_ISSUE_STATUS_NAME,
-        team_members: &team_members,
-        org: ORG,
-    }).await?;
+    if !milestone_repos.is_empty() {
+        sync_milestones(SyncMilestoneOpts {
+            api,
+            project_details: &project_details,
+            local_issue_repo_name: PROJECT_REPO_NAME,
+            local_project_milestone_status: MILESTONE_STATUS_NAME,
+            milestone_removal: config.removal_mode(MILESTONE_STATUS_NAME),
+            local_project_due_date_field: MILESTONE_DUE_DATE_FIELD_NAME,
+            project_org: PROJECT_ORG,
+            repos_to_sync: &milestone_repos,
+            roadmap_team_name: ROADMAP_TEAM_NAME,
+            only_milestone_number,
+            updated_since,
+            state,
+            audit,
+            lock,
+        }).await?;
+    }
+
+    // Sync issues and PRs according to the rules:
+    if do_sync_rules {
+        sync_rules(SyncRulesOpts {
+            api,
+            project_org: PROJECT_ORG,
+            orgs: &config.orgs,
+            config,
+            local_project_details: &project_details.tools,
+            local_issue_repo: &local_issue_repo,
+            team_group_name: TOOLS_TEAM_GROUP,
+            team_members: &team_members,
+            team_repos: &repo_names,
+            scope,
+            audit,
+            lock,
+        }).await?;
+    }
 
     Ok(())
 }
+
+struct Team {
+    members: Vec<String>,
+    /// Repos, as `owner/name`.
+    repos: Vec<String>,
+}
+
+/// Work out who is in the team and which repos (in any of the given orgs) are theirs, either
+/// from the built in lists or from the team group on GitHub, depending on the config.
+async fn team(api: &Api, orgs: &[String], config: &config::TeamConfig) -> Result<Team, anyhow::Error> {
+    let config::TeamConfig {
+        discover_members,
+        discover_repos,
+        repo_permission,
+        repo_topics,
+        include_members,
+        exclude_members,
+        include_repos,
+        exclude_repos,
+    } = config;
+
+    let (team_org, team_slug) = TOOLS_TEAM_GROUP
+        .split_once('/')
+        .expect("TOOLS_TEAM_GROUP should be 'org/slug'");
+
+    let mut members: Vec<String> = if *discover_members {
+        api::query::team::members(api, team_org, team_slug).await?
+    } else {
+        TEAM_MEMBERS.iter().map(|m| m.to_string()).collect()
+    };
+
+    let mut repos: Vec<String> = if *discover_repos {
+        api::query::team::repos(api, team_org, team_slug)
+            .await?
+            .into_iter()
+            .filter(|repo| orgs.iter().any(|org| org.eq_ignore_ascii_case(&repo.owner)) && !repo.is_archived)
+            .filter(|repo| repo_permission.is_none_or(|p| repo.permission >= p))
+            .filter(|repo| repo_topics.is_empty() || repo.topics.iter().any(|t| repo_topics.iter().any(|rt| rt.eq_ignore_ascii_case(t))))
+            .map(|repo| format!("{}/{}", repo.owner, repo.name))
+            .collect()
+    } else {
+        REPO_NAMES.iter().map(|r| utils::full_repo_name(&orgs[0], r)).collect()
+    };
+
+    let contains = |list: &[String], s: &str| list.iter().any(|l| l.eq_ignore_ascii_case(s));
+    members.extend(include_members.iter().cloned());
+    members.retain(|m| !contains(exclude_members, m));
+    members.sort_by_key(|m| m.to_lowercase());
+    members.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+    let exclude_repos: Vec<String> = exclude_repos.iter().map(|r| utils::full_repo_name(&orgs[0], r)).collect();
+    repos.extend(include_repos.iter().map(|r| utils::full_repo_name(&orgs[0], r)));
+    repos.retain(|r| !contains(&exclude_repos, r));
+    repos.sort_by_key(|r| r.to_lowercase());
+    repos.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+    // Several sources search within the team repos, so we need some.
+    if repos.is_empty() {
+        anyhow::bail!("No team repos found; check the [team] config")
+    }
+
+    if *discover_members || *discover_repos {
+        info!("👥 team has {} members and {} repos", members.len(), repos.len());
+    }
+
+    Ok(Team { members, repos })
+}
@@ -0,0 +1,682 @@
+use crate::api::{ Api, common::{ orgs_qualifier, repos_qualifier, updated_since_qualifier, FieldValue }, query::{ self, closed_things::ClosedThing, content_summaries::ContentSummary, team_prs::ReviewStatus, project_details::{ ProjectField, FieldDataType } } };
+use crate::utils::full_repo_name;
+use regex::Regex;
+use std::collections::HashMap;
+use tokio::sync::{ Mutex, OnceCell };
+
+/// A rule saying that issues and PRs found by some source belong in some
+/// status column of some project.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Used in logs, and recorded in the audit log as the reason for any changes.
+    pub name: String,
+    /// Where to find the issues and PRs that this rule applies to.
+    pub source: Source,
+    /// The number of the project to put them on. Defaults to the local project.
+    #[serde(default)]
+    pub project: Option<usize>,
+    /// The status column to put them in; the first status whose name starts with this.
+    pub status: String,
+    /// If an issue or PR matches several rules for the same project, the one with the
+    /// highest priority wins (and after that, whichever comes first).
+    #[serde(default)]
+    pub priority: i64,
+    /// Other fields to set on the project items, by field name.
+    #[serde(default)]
+    pub fields: HashMap<String, RuleFieldValue>,
+    /// Issues and PRs that this rule should ignore, on top of those ignored by every rule.
+    #[serde(default)]
+    pub exclude: Exclusions,
+}
+
+/// Issues and PRs to keep off the board. Anything matching any of these is excluded.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Exclusions {
+    /// Repos, as `name` (in our default org) or `owner/name`.
+    #[serde(default)]
+    pub repos: Vec<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Author logins. Bots can be given with or without the `[bot]` suffix.
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// Regular expressions matching titles.
+    #[serde(default, deserialize_with = "deserialize_regexes")]
+    pub titles: Vec<Regex>,
+    /// URLs of specific issues and PRs.
+    #[serde(default)]
+    pub urls: Vec<String>,
+}
+
+impl Exclusions {
+    pub fn is_empty(&self) -> bool {
+        self.repos.is_empty()
+            && self.labels.is_empty()
+            && self.authors.is_empty()
+            && self.titles.is_empty()
+            && self.urls.is_empty()
+    }
+
+    /// Should the given issue or PR be excluded? Repos given as just `name` are in `default_org`.
+    pub fn excludes(&self, default_org: &str, content: &ContentSummary) -> bool {
+        let bot_name = |login: &str| login.trim_end_matches("[bot]").to_ascii_lowercase();
+
+        self.repos.iter().any(|repo| full_repo_name(default_org, repo).eq_ignore_ascii_case(&content.repository))
+        || self.labels.iter().any(|label| content.labels.iter().any(|l| l.eq_ignore_ascii_case(label)))
+        || content.author.as_deref().is_some_and(|author| self.authors.iter().any(|a| bot_name(a) == bot_name(author)))
+        || self.titles.iter().any(|title| title.is_match(&content.title))
+        || self.urls.iter().any(|url| url.trim_end_matches('/') == content.url)
+    }
+}
+
+fn deserialize_regexes<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    let patterns: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// A value to give some project field. How it's interpreted depends on the type of the field;
+/// single select options and iterations are given by name (or `@current` for the current
+/// iteration), and dates as `YYYY-MM-DD`. Text can refer to variables that the source provides
+/// for each thing it finds as `{name}`; if that leaves nothing, the field is cleared.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum RuleFieldValue {
+    Number(f64),
+    Text(String),
+}
+
+impl RuleFieldValue {
+    /// Work out the value to give the given field, or `None` if it should be cleared.
+    pub fn to_field_value(&self, field: &ProjectField, vars: &Vars) -> Result<Option<FieldValue>, anyhow::Error> {
+        let text = match self {
+            RuleFieldValue::Number(n) => n.to_string(),
+            RuleFieldValue::Text(t) => {
+                let mut text = t.clone();
+                for (name, value) in vars {
+                    text = text.replace(&format!("{{{name}}}"), value);
+                }
+                text
+            },
+        };
+        if text.trim().is_empty() {
+            return Ok(None)
+        }
+
+        let value = match field.data_type {
+            FieldDataType::Text => FieldValue::Text(text),
+            FieldDataType::Number => match self {
+                RuleFieldValue::Number(n) => FieldValue::Number(*n),
+                RuleFieldValue::Text(_) => FieldValue::Number(text.parse()?),
+            },
+            FieldDataType::Date => {
+                time::Date::parse(&text, &date_format())?;
+                FieldValue::Date(text)
+            },
+            FieldDataType::SingleSelect => {
+                let option = field.options
+                    .iter()
+                    .find(|o| o.name.trim().eq_ignore_ascii_case(&text))
+                    .ok_or_else(|| anyhow::anyhow!("'{text}' is not an option"))?;
+                FieldValue::SingleSelect(option.id.clone())
+            },
+            FieldDataType::Iteration => {
+                let iterations = field.configuration.iter().flat_map(|c| &c.iterations);
+                let iteration = if text == "@current" {
+                    let format = date_format();
+                    let today = time::OffsetDateTime::now_utc().date();
+                    iterations
+                        .filter_map(|i| Some((i, time::Date::parse(&i.start_date, &format).ok()?)))
+                        .find(|(i, start)| *start <= today && today < *start + time::Duration::days(i.duration))
+                        .map(|(i, _)| i)
+                } else {
+                    iterations.into_iter().find(|i| i.title.trim().eq_ignore_ascii_case(&text))
+                };
+                let iteration = iteration.ok_or_else(|| anyhow::anyhow!("'{text}' is not an iteration"))?;
+                FieldValue::Iteration(iteration.id.clone())
+            },
+            FieldDataType::Other => {
+                anyhow::bail!("this type of field can't be set")
+            }
+        };
+        Ok(Some(value))
+    }
+}
+
+// Dates in project fields look like `YYYY-MM-DD`.
+fn date_format() -> Vec<time::format_description::FormatItem<'static>> {
+    time::format_description::parse("[year]-[month]-[day]").expect("should be valid date format")
+}
+
+/// Somewhere to find issues and PRs.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Source {
+    /// Open issues assigned to team members (not including milestone issues).
+    AssignedIssues {
+        /// If given, only issues which do (`true`) or don't (`false`) have an open, non-draft
+        /// PR that will close them are included.
+        #[serde(default)]
+        pr_in_review: Option<bool>,
+    },
+    /// Open PRs that the team should care about; those authored by team members in team
+    /// repos, those which have asked the team group for review at some point, those asking
+    /// team members for review, and those which touch code that the team owns in any of the
+    /// configured `code_owner_repos`. Rule fields can refer to `{blocked_reason}`, and to
+    /// `{requested_reviewers}` (the team members whose review is requested).
+    TeamPrs {
+        /// If given, only draft (`true`) or non-draft (`false`) PRs are included.
+        #[serde(default)]
+        draft: Option<bool>,
+        /// If given, only PRs at this point in the review process are included.
+        #[serde(default)]
+        review: Option<ReviewStatus>,
+        /// If given, only PRs which are (`true`) or aren't (`false`) blocked by merge
+        /// conflicts or failing required checks are included.
+        #[serde(default)]
+        blocked: Option<bool>,
+    },
+    /// Open issues in team repos that nobody is assigned to, and so which need triaging. Once
+    /// somebody is assigned, the issue no longer matches.
+    Triage {
+        /// Repos to look in, as `name` (in our default org) or `owner/name`. Defaults to the team repos.
+        #[serde(default)]
+        repos: Vec<String>,
+        /// Only include issues with no labels.
+        #[serde(default)]
+        no_labels: bool,
+        /// Only include issues which aren't in a milestone.
+        #[serde(default)]
+        no_milestone: bool,
+    },
+    /// Open PRs, and open issues that nobody is assigned to, in team repos which were opened by
+    /// people outside of the team. Rule fields can refer to `{needs_response}`, which says when
+    /// it started waiting for a response from the team once that's longer than `response_days` ago.
+    Community {
+        /// Repos to look in, as `name` (in our default org) or `owner/name`. Defaults to the team repos.
+        #[serde(default)]
+        repos: Vec<String>,
+        /// Authors to ignore, eg bots.
+        #[serde(default = "default_excluded_authors")]
+        exclude_authors: Vec<String>,
+        /// How many days something can wait for a response from the team before it's flagged.
+        #[serde(default = "default_response_days")]
+        response_days: i64,
+        /// If given, only things which are (`true`) or aren't (`false`) flagged are included.
+        #[serde(default)]
+        needs_response: Option<bool>,
+    },
+    /// Issues assigned to team members which were closed recently.
+    ClosedIssues {
+        #[serde(default)]
+        retention: Retention,
+    },
+    /// PRs authored by team members which were merged recently.
+    MergedPrs {
+        #[serde(default)]
+        retention: Retention,
+        /// Also include PRs that team members reviewed.
+        #[serde(default)]
+        include_reviewed: bool,
+        /// Also include PRs in team repos with commits co-authored by team members. This
+        /// has to look through every recently merged PR in those repos, so it's slower.
+        #[serde(default)]
+        include_coauthored: bool,
+    },
+    /// Anything found by an issue/PR search built from the given filters.
+    Search(SearchSource),
+}
+
+fn default_excluded_authors() -> Vec<String> {
+    ["dependabot", "github-actions", "renovate"].map(String::from).to_vec()
+}
+
+fn default_response_days() -> i64 {
+    7
+}
+
+/// How long closed issues and merged PRs are kept around for. Things have to satisfy
+/// every limit which is given.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Retention {
+    /// Only things closed in the last this many days.
+    #[serde(default = "default_retention_days")]
+    pub days: i64,
+    /// Only things closed since the latest release of their repo.
+    #[serde(default)]
+    pub since_last_release: bool,
+    /// At most this many things, keeping the most recently closed.
+    #[serde(default)]
+    pub max: Option<usize>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention { days: default_retention_days(), since_last_release: false, max: None }
+    }
+}
+
+impl Retention {
+    /// The earliest time that things can have been closed at.
+    fn closed_since(&self) -> time::OffsetDateTime {
+        time::OffsetDateTime::now_utc() - time::Duration::days(self.days)
+    }
+}
+
+fn default_retention_days() -> i64 {
+    28
+}
+
+/// Filters for a search. Each list matches things which match any of its entries, and
+/// a thing has to match every filter which is given.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchSource {
+    /// Any extra search qualifiers, eg `"no:milestone sort:updated-desc"`.
+    #[serde(default)]
+    pub query: String,
+    /// Only issues or only PRs.
+    #[serde(default, rename = "type")]
+    pub content_type: Option<ContentType>,
+    #[serde(default)]
+    pub state: Option<SearchState>,
+    /// Only draft (`true`) or non-draft (`false`) PRs.
+    #[serde(default)]
+    pub draft: Option<bool>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Repos to search in, either as `name` (in our default org) or `owner/name`. If none
+    /// are given, we search all of our orgs.
+    #[serde(default)]
+    pub repos: Vec<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub assignees: Vec<String>,
+    /// Users, or teams given as `org/team`, whose review has been requested.
+    #[serde(default)]
+    pub review_requested: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentType {
+    Issue,
+    Pr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchState {
+    Open,
+    Closed,
+    Merged,
+}
+
+impl SearchSource {
+    /// Build the search query string for this source.
+    fn to_query(&self, orgs: &[String], updated_since: Option<time::OffsetDateTime>) -> String {
+        let mut query = self.query.clone();
+        let mut push = |qualifier: String| {
+            query.push(' ');
+            query.push_str(&qualifier);
+        };
+
+        match self.content_type {
+            Some(ContentType::Issue) => push("is:issue".to_owned()),
+            Some(ContentType::Pr) => push("is:pr".to_owned()),
+            None => {}
+        }
+        match self.state {
+            Some(SearchState::Open) => push("is:open".to_owned()),
+            Some(SearchState::Closed) => push("is:closed".to_owned()),
+            Some(SearchState::Merged) => push("is:merged".to_owned()),
+            None => {}
+        }
+        if let Some(draft) = self.draft {
+            push(format!("draft:{draft}"));
+        }
+        if !self.labels.is_empty() {
+            let labels: Vec<_> = self.labels.iter().map(|l| format!("\"{l}\"")).collect();
+            push(format!("label:{}", labels.join(",")));
+        }
+        if self.repos.is_empty() {
+            push(orgs_qualifier(orgs));
+        }
+        for repo in &self.repos {
+            push(format!("repo:{}", full_repo_name(&orgs[0], repo)));
+        }
+        for author in &self.authors {
+            push(format!("author:{author}"));
+        }
+        for assignee in &self.assignees {
+            push(format!("assignee:{assignee}"));
+        }
+        for reviewer in &self.review_requested {
+            match reviewer.contains('/') {
+                true => push(format!("team-review-requested:{reviewer}")),
+                false => push(format!("review-requested:{reviewer}")),
+            }
+        }
+        push(updated_since_qualifier(updated_since));
+
+        query.trim().to_owned()
+    }
+}
+
+/// What sources need to know in order to find things.
+#[derive(Debug, Copy, Clone)]
+pub struct SourceOpts<'a> {
+    pub api: &'a Api,
+    /// The orgs to look for issues and PRs in. Repos given as just `name` are
+    /// in the first of these, our default org.
+    pub orgs: &'a [String],
+    pub team_members: &'a [String],
+    /// Team repos, as `owner/name`.
+    pub team_repos: &'a [String],
+    pub team_group_name: &'a str,
+    /// The repo (as `owner/name`) that milestone issues are created in.
+    pub local_issue_repo: &'a str,
+    /// Repos whose CODEOWNERS files we check for code that the team owns.
+    pub code_owner_repos: &'a [String],
+    /// If given, sources only look for things updated since this time.
+    pub updated_since: Option<time::OffsetDateTime>,
+}
+
+/// Variables that a source provides for some issue or PR, which rule fields can refer to.
+pub type Vars = HashMap<&'static str, String>;
+
+/// An issue or PR found by some source.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub id: String,
+    pub vars: Vars,
+}
+
+impl Match {
+    fn new(id: String) -> Match {
+        Match { id, vars: Vars::new() }
+    }
+}
+
+/// Finds the issues and PRs for sources. Several sources can be built on the
+/// same query, so query results are cached here for the duration of a sync.
+pub struct Sources<'a> {
+    opts: SourceOpts<'a>,
+    team_prs: OnceCell<Vec<query::team_prs::Issue>>,
+    latest_releases: Mutex<HashMap<String, Option<time::OffsetDateTime>>>,
+}
+
+impl <'a> Sources<'a> {
+    pub fn new(opts: SourceOpts<'a>) -> Sources<'a> {
+        Sources { opts, team_prs: OnceCell::new(), latest_releases: Mutex::new(HashMap::new()) }
+    }
+
+    /// Find every issue and PR from the given source.
+    pub async fn find(&self, source: &Source) -> Result<Vec<Match>, anyhow::Error> {
+        let SourceOpts {
+            api,
+            orgs,
+            team_members,
+            team_repos,
+            team_group_name,
+            local_issue_repo,
+            code_owner_repos,
+            updated_since
+        } = self.opts;
+
+        let full_repo_names = |repos: &[String]| -> Vec<String> {
+            repos.iter().map(|repo| full_repo_name(&orgs[0], repo)).collect()
+        };
+
+        match source {
+            Source::AssignedIssues { pr_in_review } => {
+                let issues = query::open_assigned_issues::run(api, orgs, team_members, local_issue_repo, updated_since).await?;
+                Ok(issues
+                    .into_iter()
+                    .filter(|issue| pr_in_review.is_none_or(|pr_in_review| issue.has_pr_in_review == pr_in_review))
+                    .map(|issue| Match::new(issue.id))
+                    .collect())
+            },
+            Source::TeamPrs { draft, review, blocked } => {
+                let team_prs = self.team_prs
+                    .get_or_try_init(|| async {
+                        let mut team_prs = query::team_prs::run(api, orgs, team_group_name, team_members, team_repos, updated_since).await?;
+                        if !code_owner_repos.is_empty() {
+                            let repos = full_repo_names(code_owner_repos);
+                            let owners: Vec<&str> = std::iter::once(team_group_name)
+                                .chain(team_members.iter().map(|m| &**m))
+                                .collect();
+                            for pr in query::team_prs::code_owned(api, &repos, &owners, updated_since).await? {
+                                if !team_prs.iter().any(|p| p.id == pr.id) {
+                                    team_prs.push(pr);
+                                }
+                            }
+                        }
+                        Ok::<_, anyhow::Error>(team_prs)
+                    })
+                    .await?;
+                Ok(team_prs
+                    .iter()
+                    .filter(|pr| draft.is_none_or(|draft| pr.draft == draft))
+                    .filter(|pr| review.is_none_or(|review| pr.review_status() == review))
+                    .map(|pr| (pr, pr.blocked_reason()))
+                    .filter(|(_, reason)| blocked.is_none_or(|blocked| reason.is_some() == blocked))
+                    .map(|(pr, reason)| Match {
+                        id: pr.id.clone(),
+                        vars: Vars::from([
+                            ("blocked_reason", reason.unwrap_or_default()),
+                            ("requested_reviewers", pr.requested_users(team_members).join(", ")),
+                        ]),
+                    })
+                    .collect())
+            },
+            Source::Triage { repos, no_labels, no_milestone } => {
+                let repos = if repos.is_empty() { team_repos.to_vec() } else { full_repo_names(repos) };
+                let mut query = String::from("is:issue is:open no:assignee");
+                if *no_labels {
+                    query.push_str(" no:label");
+                }
+                if *no_milestone {
+                    query.push_str(" no:milestone");
+                }
+                query.push(' ');
+                query.push_str(&repos_qualifier(&repos));
+                query.push(' ');
+                query.push_str(&updated_since_qualifier(updated_since));
+                let ids = query::search::run(api, query.trim()).await?;
+                Ok(ids.into_iter().map(Match::new).collect())
+            },
+            Source::Community { repos, exclude_authors, response_days, needs_response } => {
+                let repos = if repos.is_empty() { team_repos.to_vec() } else { full_repo_names(repos) };
+                let contributions = query::community::run(api, &repos, team_members, exclude_authors, updated_since).await?;
+                let now = time::OffsetDateTime::now_utc();
+                Ok(contributions
+                    .into_iter()
+                    .map(|c| {
+                        // This doesn't change from day to day, so that we don't update the field every sync.
+                        let flag = c.waiting_since
+                            .filter(|since| (now - *since).whole_days() >= *response_days)
+                            .map(|since| format!("waiting since {}", since.date()));
+                        (c, flag)
+                    })
+                    .filter(|(_, flag)| needs_response.is_none_or(|needs_response| flag.is_some() == needs_response))
+                    .map(|(c, flag)| Match {
+                        id: c.id,
+                        vars: Vars::from([("needs_response", flag.unwrap_or_default())]),
+                    })
+                    .collect())
+            },
+            Source::ClosedIssues { retention } => {
+                let closed_issues = query::closed_things::closed_issues(api, orgs, team_members, retention.closed_since(), updated_since).await?;
+                self.retain(retention, closed_issues).await
+            },
+            Source::MergedPrs { retention, include_reviewed, include_coauthored } => {
+                let closed_since = retention.closed_since();
+                let mut merged_prs = query::closed_things::merged_prs(api, orgs, team_members, *include_reviewed, closed_since, updated_since).await?;
+                if *include_coauthored {
+                    let coauthored = query::closed_things::coauthored_merged_prs(api, team_members, team_repos, closed_since, updated_since).await?;
+                    for pr in coauthored {
+                        if !merged_prs.iter().any(|p| p.id == pr.id) {
+                            merged_prs.push(pr);
+                        }
+                    }
+                }
+                self.retain(retention, merged_prs).await
+            },
+            Source::Search(search) => {
+                let ids = query::search::run(api, &search.to_query(orgs, updated_since)).await?;
+                Ok(ids.into_iter().map(Match::new).collect())
+            },
+        }
+    }
+
+    /// Apply the release and count limits of some retention to closed things (they've
+    /// already been searched for within its time window), and return those left.
+    async fn retain(&self, retention: &Retention, mut things: Vec<ClosedThing>) -> Result<Vec<Match>, anyhow::Error> {
+        if retention.since_last_release {
+            let mut latest_releases = self.latest_releases.lock().await;
+            for thing in &things {
+                if !latest_releases.contains_key(&thing.repository) {
+                    let latest_release = query::latest_release::run(self.opts.api, &thing.repository).await?;
+                    latest_releases.insert(thing.repository.clone(), latest_release);
+                }
+            }
+            // Repos which have never been released keep everything.
+            things.retain(|thing| latest_releases[&thing.repository].is_none_or(|at| thing.closed_at >= at));
+        }
+        if let Some(max) = retention.max {
+            things.sort_by_key(|thing| std::cmp::Reverse(thing.closed_at));
+            things.truncate(max);
+        }
+        Ok(things.into_iter().map(|thing| Match::new(thing.id)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orgs() -> Vec<String> {
+        vec!["paritytech".to_owned(), "other-org".to_owned()]
+    }
+
+    fn strings(strs: &[&str]) -> Vec<String> {
+        strs.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn summary() -> ContentSummary {
+        ContentSummary {
+            title: "Bump serde from 1.0.1 to 1.0.2".to_owned(),
+            url: "https://github.com/paritytech/subxt/pull/12".to_owned(),
+            repository: "paritytech/subxt".to_owned(),
+            author: Some("dependabot[bot]".to_owned()),
+            labels: strings(&["A-dependencies"]),
+            updated_at: time::OffsetDateTime::UNIX_EPOCH,
+            closing_issue_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn empty_exclusions_exclude_nothing() {
+        let exclusions = Exclusions::default();
+        assert!(exclusions.is_empty());
+        assert!(!exclusions.excludes("paritytech", &summary()));
+    }
+
+    #[test]
+    fn exclusions_match_repos_in_the_default_org() {
+        let exclusions = |repos: &[&str]| Exclusions { repos: strings(repos), ..Exclusions::default() };
+        assert!(exclusions(&["subxt"]).excludes("paritytech", &summary()));
+        assert!(exclusions(&["ParityTech/Subxt"]).excludes("paritytech", &summary()));
+        assert!(!exclusions(&["subxt"]).excludes("other-org", &summary()));
+        assert!(!exclusions(&["other-org/subxt"]).excludes("paritytech", &summary()));
+    }
+
+    #[test]
+    fn exclusions_match_labels_ignoring_case() {
+        let exclusions = |labels: &[&str]| Exclusions { labels: strings(labels), ..Exclusions::default() };
+        assert!(exclusions(&["wontfix", "a-dependencies"]).excludes("paritytech", &summary()));
+        assert!(!exclusions(&["wontfix"]).excludes("paritytech", &summary()));
+    }
+
+    #[test]
+    fn exclusions_match_bots_with_or_without_suffix() {
+        let exclusions = |authors: &[&str]| Exclusions { authors: strings(authors), ..Exclusions::default() };
+        assert!(exclusions(&["dependabot"]).excludes("paritytech", &summary()));
+        assert!(exclusions(&["dependabot[bot]"]).excludes("paritytech", &summary()));
+        assert!(!exclusions(&["jsdw"]).excludes("paritytech", &summary()));
+        let ghost = ContentSummary { author: None, ..summary() };
+        assert!(!exclusions(&["dependabot"]).excludes("paritytech", &ghost));
+    }
+
+    #[test]
+    fn exclusions_match_titles_and_urls() {
+        let titles = Exclusions { titles: vec![Regex::new("^Bump ").expect("valid regex")], ..Exclusions::default() };
+        assert!(titles.excludes("paritytech", &summary()));
+        let urls = |urls: &[&str]| Exclusions { urls: strings(urls), ..Exclusions::default() };
+        assert!(urls(&["https://github.com/paritytech/subxt/pull/12/"]).excludes("paritytech", &summary()));
+        assert!(!urls(&["https://github.com/paritytech/subxt/pull/1"]).excludes("paritytech", &summary()));
+    }
+
+    #[test]
+    fn search_without_repos_covers_every_org() {
+        let search = SearchSource {
+            query: "no:milestone".to_owned(),
+            content_type: Some(ContentType::Issue),
+            state: Some(SearchState::Open),
+            ..SearchSource::default()
+        };
+        assert_eq!(search.to_query(&orgs(), None), "no:milestone is:issue is:open org:paritytech org:other-org");
+    }
+
+    #[test]
+    fn search_with_repos_only_covers_them() {
+        let search = SearchSource {
+            repos: strings(&["subxt", "other-org/thing"]),
+            ..SearchSource::default()
+        };
+        assert_eq!(search.to_query(&orgs(), None), "repo:paritytech/subxt repo:other-org/thing");
+    }
+
+    #[test]
+    fn search_includes_every_filter() {
+        let search = SearchSource {
+            content_type: Some(ContentType::Pr),
+            state: Some(SearchState::Merged),
+            draft: Some(false),
+            labels: strings(&["A-bug", "needs review"]),
+            authors: strings(&["jsdw"]),
+            assignees: strings(&["niklasad1"]),
+            review_requested: strings(&["lexnv", "paritytech/subxt-team"]),
+            ..SearchSource::default()
+        };
+        assert_eq!(
+            search.to_query(&orgs(), None),
+            "is:pr is:merged draft:false label:\"A-bug\",\"needs review\" org:paritytech org:other-org \
+             author:jsdw assignee:niklasad1 review-requested:lexnv team-review-requested:paritytech/subxt-team"
+        );
+    }
+
+    #[test]
+    fn search_can_be_limited_to_recent_updates() {
+        let since = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).expect("valid timestamp");
+        let query = SearchSource::default().to_query(&orgs(), Some(since));
+        assert!(query.starts_with("org:paritytech org:other-org updated:>=2023-11-14T22:13:20"), "{query}");
+    }
+
+    #[test]
+    fn unknown_source_fields_are_rejected() {
+        let source = |toml: &str| toml::from_str::<HashMap<String, Source>>(toml);
+        assert!(source(r#"source = { kind = "search", labels = ["A-bug"] }"#).is_ok());
+        assert!(source(r#"source = { kind = "search", label = ["A-bug"] }"#).is_err());
+        assert!(source(r#"source = { kind = "team_prs", draft = true }"#).is_ok());
+        assert!(source(r#"source = { kind = "team_prs", drafts = true }"#).is_err());
+        assert!(source(r#"source = { kind = "no_such_source" }"#).is_err());
+    }
+}
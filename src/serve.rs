@@ -0,0 +1,374 @@
+use crate::api::Api;
+use crate::config::Config;
+use crate::{ SyncOpts, SyncTarget };
+use axum::{ Router, routing::post, extract::State, http::{ HeaderMap, StatusCode }, body::Bytes };
+use hmac::{ Hmac, Mac };
+use sha2::Sha256;
+use std::{ net::SocketAddr, sync::Arc, time::Duration };
+use tracing::{ info, warn, error };
+
+// GitHub's search index can lag a little behind the events that it sends us, so
+// wait a bit before syncing in response to one.
+const WEBHOOK_SYNC_DELAY: Duration = Duration::from_secs(10);
+
+// The actions on issues and PRs which can change where they belong on the boards. Others
+// (like new commits being pushed to a PR) are left for the periodic syncs to pick up.
+const ISSUE_ACTIONS: &[&str] = &[
+    "opened", "edited", "closed", "reopened", "assigned", "unassigned",
+    "labeled", "unlabeled", "milestoned", "demilestoned",
+];
+const PULL_REQUEST_ACTIONS: &[&str] = &[
+    "opened", "edited", "closed", "reopened", "assigned", "unassigned", "labeled", "unlabeled",
+    "ready_for_review", "converted_to_draft", "review_requested", "review_request_removed",
+];
+const PULL_REQUEST_REVIEW_ACTIONS: &[&str] = &["submitted", "dismissed"];
+
+#[derive(Debug, clap::Args)]
+pub struct ServeOpts {
+    /// The address to listen for webhook deliveries on. Deliveries are expected at `/webhook`.
+    #[arg(long, default_value = "0.0.0.0:8080")]
+    addr: SocketAddr,
+    /// The secret configured on the GitHub webhook. Used to verify the `X-Hub-Signature-256` header.
+    #[arg(long, env = "GITHUB_WEBHOOK_SECRET", hide_env_values = true)]
+    webhook_secret: String,
+    /// How often (in minutes) to run a full sync, to catch anything that webhooks missed.
+    #[arg(long, default_value_t = 15)]
+    full_sync_interval_mins: u64,
+}
+
+struct Server {
+    api: Api,
+    sync_opts: SyncOpts,
+    webhook_secret: String,
+    // Syncs are run one at a time so that they don't trip over each other.
+    sync_lock: tokio::sync::Mutex<()>,
+    // Things that webhook deliveries have asked us to sync, which haven't been yet.
+    pending: std::sync::Mutex<Vec<SyncTarget>>,
+    // Notified whenever something is added to `pending`.
+    pending_added: tokio::sync::Notify,
+}
+
+impl Server {
+    async fn sync(&self, target: SyncTarget) {
+        let _guard = self.sync_lock.lock().await;
+        info!("🔄 syncing {target:?}");
+        if let Err(e) = crate::sync(&self.api, &self.sync_opts, &target).await {
+            error!("🛑 failed to sync {target:?}: {e:?}");
+        }
+    }
+
+    /// Queue something up to be synced, unless it's already waiting to be. Issues and PRs
+    /// are collected up so that they're all synced together.
+    fn add_pending(&self, target: SyncTarget) {
+        let mut pending = self.pending.lock().expect("pending lock poisoned");
+        add_target(&mut pending, target);
+        self.pending_added.notify_one();
+    }
+
+    /// Sync whatever webhook deliveries have asked for, forever. Anything which comes in
+    /// while we're waiting or syncing is collected up and synced in the next batch.
+    async fn sync_pending(&self) {
+        loop {
+            self.pending_added.notified().await;
+            tokio::time::sleep(WEBHOOK_SYNC_DELAY).await;
+
+            let targets = std::mem::take(&mut *self.pending.lock().expect("pending lock poisoned"));
+            for target in targets {
+                self.sync(target).await;
+            }
+        }
+    }
+
+    /// Is the given org one that we look for issues and PRs in? The config is re-read
+    /// each time, like it is for each sync. If it can't be read, we assume so, and leave
+    /// the sync to complain about it.
+    fn is_our_org(&self, org: &str) -> bool {
+        match Config::load(self.sync_opts.config.as_deref()) {
+            Ok(config) => config.orgs.iter().any(|o| o.eq_ignore_ascii_case(org)),
+            Err(_) => true
+        }
+    }
+}
+
+/// Listen for webhook deliveries and sync whatever they point at, running a full sync
+/// every so often as a safety net.
+pub async fn run(api: Api, sync_opts: SyncOpts, opts: ServeOpts) -> Result<(), anyhow::Error> {
+    let server = Arc::new(Server {
+        api,
+        sync_opts,
+        webhook_secret: opts.webhook_secret,
+        sync_lock: tokio::sync::Mutex::new(()),
+        pending: std::sync::Mutex::new(Vec::new()),
+        pending_added: tokio::sync::Notify::new(),
+    });
+
+    let pending_server = server.clone();
+    tokio::spawn(async move { pending_server.sync_pending().await });
+
+    // Periodic full sync. The first tick completes immediately, so we sync everything on startup too.
+    let full_sync_server = server.clone();
+    let full_sync_interval = Duration::from_secs(opts.full_sync_interval_mins * 60);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(full_sync_interval);
+        loop {
+            interval.tick().await;
+            full_sync_server.sync(SyncTarget::Everything).await;
+        }
+    });
+
+    let app = Router::new()
+        .route("/webhook", post(handle_webhook))
+        .with_state(server);
+
+    let listener = tokio::net::TcpListener::bind(opts.addr).await?;
+    info!("listening for webhook deliveries on {}", opts.addr);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+async fn handle_webhook(State(server): State<Arc<Server>>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if !is_signature_valid(&server.webhook_secret, header("X-Hub-Signature-256"), &body) {
+        warn!("🛑 rejecting webhook delivery with a missing or invalid signature");
+        return StatusCode::UNAUTHORIZED
+    }
+
+    let event = header("X-GitHub-Event").unwrap_or_default();
+    let target = match sync_target_for_event(event, &body) {
+        Ok(Some((owner, target))) if server.is_our_org(&owner) => target,
+        // Nothing for us to do with this event:
+        Ok(_) => return StatusCode::OK,
+        Err(e) => {
+            warn!("🛑 could not decode `{event}` webhook delivery: {e}");
+            return StatusCode::BAD_REQUEST
+        }
+    };
+
+    // Respond straight away; GitHub gives up on deliveries that take more than 10s.
+    server.add_pending(target);
+
+    StatusCode::ACCEPTED
+}
+
+/// Add a target to the list of things to sync, unless it's already there.
+fn add_target(targets: &mut Vec<SyncTarget>, target: SyncTarget) {
+    let existing_ids = targets.iter_mut().find_map(|t| match t {
+        SyncTarget::Items(ids) => Some(ids),
+        _ => None
+    });
+    match (target, existing_ids) {
+        (SyncTarget::Items(ids), Some(existing_ids)) => {
+            for id in ids {
+                if !existing_ids.contains(&id) {
+                    existing_ids.push(id);
+                }
+            }
+        },
+        (target, _) => {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+}
+
+/// Check the `X-Hub-Signature-256` header (which looks like `sha256=<hex HMAC of body>`).
+fn is_signature_valid(secret: &str, signature: Option<&str>, body: &[u8]) -> bool {
+    let Some(signature) = signature.and_then(|s| s.strip_prefix("sha256=")) else { return false };
+    let Ok(signature) = hex::decode(signature) else { return false };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Work out what needs syncing given some webhook event, along with the org that it came
+/// from. Returns `None` if the event isn't something we care about.
+fn sync_target_for_event(event: &str, body: &[u8]) -> Result<Option<(String, SyncTarget)>, anyhow::Error> {
+    // The shapes we want to deserialize to.
+    #[derive(serde::Deserialize)]
+    struct MilestoneEvent {
+        milestone: EventMilestone,
+        repository: EventRepository
+    }
+    #[derive(serde::Deserialize)]
+    struct EventMilestone {
+        number: usize
+    }
+    #[derive(serde::Deserialize)]
+    struct EventRepository {
+        name: String,
+        owner: EventOwner
+    }
+    #[derive(serde::Deserialize)]
+    struct EventOwner {
+        login: String
+    }
+    #[derive(serde::Deserialize)]
+    struct IssueEvent {
+        action: String,
+        issue: EventNode,
+        repository: EventRepository
+    }
+    #[derive(serde::Deserialize)]
+    struct PullRequestEvent {
+        action: String,
+        pull_request: EventNode,
+        repository: EventRepository
+    }
+    #[derive(serde::Deserialize)]
+    struct EventNode {
+        node_id: String
+    }
+
+    let (owner, target) = match event {
+        "milestone" => {
+            let ev: MilestoneEvent = serde_json::from_slice(body)?;
+            // Which repos are the team's can depend on the config, so the sync
+            // itself ignores milestones in any other repos.
+            let repo = format!("{}/{}", ev.repository.owner.login, ev.repository.name);
+            (ev.repository.owner.login, SyncTarget::Milestone { repo, number: ev.milestone.number })
+        },
+        "issues" => {
+            let ev: IssueEvent = serde_json::from_slice(body)?;
+            if !ISSUE_ACTIONS.contains(&&*ev.action) {
+                return Ok(None)
+            }
+            (ev.repository.owner.login, SyncTarget::Items(vec![ev.issue.node_id]))
+        },
+        "pull_request" | "pull_request_review" => {
+            let ev: PullRequestEvent = serde_json::from_slice(body)?;
+            let actions = if event == "pull_request" { PULL_REQUEST_ACTIONS } else { PULL_REQUEST_REVIEW_ACTIONS };
+            if !actions.contains(&&*ev.action) {
+                return Ok(None)
+            }
+            (ev.repository.owner.login, SyncTarget::Items(vec![ev.pull_request.node_id]))
+        },
+        _ => return Ok(None)
+    };
+
+    Ok(Some((owner, target)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from GitHub's docs on validating webhook deliveries.
+    const SECRET: &str = "It's a Secret to Everybody";
+    const BODY: &[u8] = b"Hello, World!";
+    const SIGNATURE: &str = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn valid_signature_is_accepted() {
+        assert!(is_signature_valid(SECRET, Some(SIGNATURE), BODY));
+    }
+
+    #[test]
+    fn invalid_signatures_are_rejected() {
+        assert!(!is_signature_valid("some other secret", Some(SIGNATURE), BODY));
+        assert!(!is_signature_valid(SECRET, Some(SIGNATURE), b"Hello, World?"));
+        assert!(!is_signature_valid(SECRET, Some(SIGNATURE.trim_start_matches("sha256=")), BODY));
+        assert!(!is_signature_valid(SECRET, Some("sha256=not hex"), BODY));
+        assert!(!is_signature_valid(SECRET, None, BODY));
+    }
+
+    fn target_for(event: &str, body: &str) -> Option<(String, SyncTarget)> {
+        sync_target_for_event(event, body.as_bytes()).expect("valid event")
+    }
+
+    #[test]
+    fn milestone_events_sync_the_milestone() {
+        let body = r#"{
+            "action": "edited",
+            "milestone": { "number": 12, "title": "v1.0" },
+            "repository": { "name": "subxt", "owner": { "login": "paritytech" } }
+        }"#;
+        let target = SyncTarget::Milestone { repo: "paritytech/subxt".to_owned(), number: 12 };
+        assert_eq!(target_for("milestone", body), Some(("paritytech".to_owned(), target)));
+    }
+
+    #[test]
+    fn issue_events_sync_the_issue() {
+        let body = r#"{
+            "action": "labeled",
+            "issue": { "node_id": "I_1", "number": 5 },
+            "repository": { "name": "subxt", "owner": { "login": "paritytech" } }
+        }"#;
+        let target = SyncTarget::Items(vec!["I_1".to_owned()]);
+        assert_eq!(target_for("issues", body), Some(("paritytech".to_owned(), target)));
+    }
+
+    #[test]
+    fn pull_request_events_sync_the_pr() {
+        let body = r#"{
+            "action": "ready_for_review",
+            "pull_request": { "node_id": "PR_1", "number": 7 },
+            "repository": { "name": "jsonrpsee", "owner": { "login": "paritytech" } }
+        }"#;
+        let target = SyncTarget::Items(vec!["PR_1".to_owned()]);
+        assert_eq!(target_for("pull_request", body), Some(("paritytech".to_owned(), target)));
+    }
+
+    #[test]
+    fn pull_request_review_events_sync_the_pr() {
+        let body = r#"{
+            "action": "submitted",
+            "review": { "state": "approved" },
+            "pull_request": { "node_id": "PR_1", "number": 7 },
+            "repository": { "name": "jsonrpsee", "owner": { "login": "paritytech" } }
+        }"#;
+        let target = SyncTarget::Items(vec!["PR_1".to_owned()]);
+        assert_eq!(target_for("pull_request_review", body), Some(("paritytech".to_owned(), target)));
+    }
+
+    #[test]
+    fn actions_which_cant_change_routing_are_ignored() {
+        let pr_body = r#"{
+            "action": "synchronize",
+            "pull_request": { "node_id": "PR_1" },
+            "repository": { "name": "subxt", "owner": { "login": "paritytech" } }
+        }"#;
+        assert_eq!(target_for("pull_request", pr_body), None);
+        let review_body = r#"{
+            "action": "edited",
+            "pull_request": { "node_id": "PR_1" },
+            "repository": { "name": "subxt", "owner": { "login": "paritytech" } }
+        }"#;
+        assert_eq!(target_for("pull_request_review", review_body), None);
+        let issue_body = r#"{
+            "action": "pinned",
+            "issue": { "node_id": "I_1" },
+            "repository": { "name": "subxt", "owner": { "login": "paritytech" } }
+        }"#;
+        assert_eq!(target_for("issues", issue_body), None);
+    }
+
+    #[test]
+    fn other_events_are_ignored() {
+        assert_eq!(target_for("ping", r#"{ "zen": "Keep it logically awesome." }"#), None);
+    }
+
+    #[test]
+    fn malformed_events_are_errors() {
+        assert!(sync_target_for_event("issues", br#"{ "action": "opened" }"#).is_err());
+        assert!(sync_target_for_event("milestone", b"not json").is_err());
+    }
+
+    #[test]
+    fn pending_items_are_synced_together() {
+        let mut targets = Vec::new();
+        add_target(&mut targets, SyncTarget::Items(vec!["I_1".to_owned()]));
+        add_target(&mut targets, SyncTarget::Everything);
+        add_target(&mut targets, SyncTarget::Items(vec!["PR_1".to_owned()]));
+        add_target(&mut targets, SyncTarget::Items(vec!["I_1".to_owned()]));
+        add_target(&mut targets, SyncTarget::Everything);
+        assert_eq!(targets, vec![
+            SyncTarget::Items(vec!["I_1".to_owned(), "PR_1".to_owned()]),
+            SyncTarget::Everything,
+        ]);
+    }
+}
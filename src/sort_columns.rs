@@ -0,0 +1,180 @@
+use crate::api::{ Api, query::{ self, project_details::ToolsProject, project_items::ProjectItem } };
+use crate::audit::AuditLog;
+use crate::config::{ ColumnConfig, Config, SortKey };
+use crate::utils;
+use std::cmp::{ Ordering, Reverse };
+use tracing::info;
+
+pub struct SortColumnsOpts<'a> {
+    pub api: &'a Api,
+    /// The org that the project lives in.
+    pub org: &'a str,
+    /// How each column should be sorted.
+    pub config: &'a Config,
+    pub project_details: &'a ToolsProject,
+    /// The status columns to sort. Those with no sort keys configured are left alone.
+    pub statuses: &'a [&'a str],
+    pub audit: &'a AuditLog,
+    /// The name of the stage doing the sorting, for the audit log.
+    pub stage: &'a str,
+}
+
+/// Reorder the items in each of the given status columns according to their configured
+/// sort keys, moving as few items as possible to get them into the right order.
+pub async fn sort_columns(opts: SortColumnsOpts<'_>) -> Result<(), anyhow::Error> {
+    let SortColumnsOpts {
+        api,
+        org,
+        config,
+        project_details,
+        statuses,
+        audit,
+        stage
+    } = opts;
+
+    let columns: Vec<(&str, &ColumnConfig)> = statuses
+        .iter()
+        .filter_map(|&status| Some((status, config.column(status)?)))
+        .filter(|(_, column)| !column.sort.is_empty())
+        .collect();
+    if columns.is_empty() {
+        return Ok(())
+    }
+
+    // Fetch the items again, since they've probably just been changed.
+    let all_items = query::project_items::run(api, org, project_details.number).await?;
+    let mutations = audit.mutations(api, stage);
+
+    for (status, column) in columns {
+        let status_id = utils::find_status_id(project_details, status)?;
+        let current: Vec<&ProjectItem> = all_items
+            .iter()
+            .filter(|item| !item.is_archived && item.status_field_value_id.as_deref() == Some(status_id))
+            .collect();
+
+        // A stable sort, so that items which tie keep their current order.
+        let mut desired: Vec<usize> = (0..current.len()).collect();
+        desired.sort_by(|&a, &b| compare_items(current[a], current[b], column));
+
+        let keep = longest_increasing_subsequence(&desired);
+        let to_move: Vec<usize> = (0..desired.len()).filter(|&pos| !keep[pos]).collect();
+        if to_move.is_empty() {
+            continue
+        }
+
+        info!("🔀 reordering {} items in `{status}`", to_move.len());
+        let reason = format!("sorting `{status}`");
+        for pos in to_move {
+            let item = current[desired[pos]];
+            let after_id = pos.checked_sub(1).map(|prev| &*current[desired[prev]].item_id);
+            let previous_after_id = desired[pos].checked_sub(1).map(|prev| &*current[prev].item_id);
+            mutations.move_item(&reason, &project_details.id, &item.item_id, previous_after_id, after_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare two items by each of the column's sort keys in turn.
+fn compare_items(a: &ProjectItem, b: &ProjectItem, column: &ColumnConfig) -> Ordering {
+    column.sort.iter().fold(Ordering::Equal, |ordering, key| {
+        ordering.then_with(|| match key {
+            SortKey::OldestFirst => {
+                some_first(a.content_details().map(|d| d.created_at), b.content_details().map(|d| d.created_at))
+            },
+            SortKey::MilestoneDueDate => {
+                some_first(a.content_details().and_then(|d| d.milestone_due_on), b.content_details().and_then(|d| d.milestone_due_on))
+            },
+            SortKey::LabelPriority => {
+                some_first(label_priority(a, &column.label_priority), label_priority(b, &column.label_priority))
+            },
+            SortKey::LastUpdated => {
+                some_first(a.content_updated_at.map(Reverse), b.content_updated_at.map(Reverse))
+            },
+        })
+    })
+}
+
+// Order by the values given, putting anything without one last.
+fn some_first<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    (a.is_none(), a).cmp(&(b.is_none(), b))
+}
+
+// The position of the highest priority label that the item has.
+fn label_priority(item: &ProjectItem, priorities: &[String]) -> Option<usize> {
+    let labels = &item.content_details()?.labels;
+    priorities.iter().position(|p| labels.iter().any(|l| l.eq_ignore_ascii_case(p)))
+}
+
+/// Given the current position of each item in the order we want them in, mark the longest
+/// run of items which are already in the right order relative to each other. Only the
+/// other items need to move. Columns are small, so the simple O(n²) approach is fine.
+fn longest_increasing_subsequence(positions: &[usize]) -> Vec<bool> {
+    let len = positions.len();
+    let mut lengths = vec![1; len];
+    let mut prev: Vec<Option<usize>> = vec![None; len];
+    for i in 0..len {
+        for j in 0..i {
+            if positions[j] < positions[i] && lengths[j] + 1 > lengths[i] {
+                lengths[i] = lengths[j] + 1;
+                prev[i] = Some(j);
+            }
+        }
+    }
+
+    let mut keep = vec![false; len];
+    let mut next = (0..len).max_by_key(|&i| lengths[i]);
+    while let Some(i) = next {
+        keep[i] = true;
+        next = prev[i];
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The desired positions of the items that are kept.
+    fn kept(positions: &[usize]) -> Vec<usize> {
+        let keep = longest_increasing_subsequence(positions);
+        positions.iter().zip(keep).filter(|(_, keep)| *keep).map(|(&p, _)| p).collect()
+    }
+
+    #[test]
+    fn nothing_moves_if_already_sorted() {
+        assert_eq!(kept(&[]), Vec::<usize>::new());
+        assert_eq!(kept(&[0]), vec![0]);
+        assert_eq!(kept(&[0, 1, 2, 3]), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn one_item_out_of_place_is_the_only_one_moved() {
+        // The last item belongs at the top:
+        assert_eq!(kept(&[1, 2, 3, 0]), vec![1, 2, 3]);
+        // The top item belongs at the bottom:
+        assert_eq!(kept(&[3, 0, 1, 2]), vec![0, 1, 2]);
+        // Two neighbours are swapped:
+        assert_eq!(kept(&[0, 2, 1, 3]).len(), 3);
+    }
+
+    #[test]
+    fn reversed_column_keeps_one_item() {
+        assert_eq!(kept(&[3, 2, 1, 0]).len(), 1);
+    }
+
+    #[test]
+    fn kept_items_are_in_order() {
+        let kept = kept(&[4, 0, 5, 1, 2, 7, 3, 6]);
+        assert_eq!(kept.len(), 5);
+        assert!(kept.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn missing_values_sort_last() {
+        assert_eq!(some_first(Some(1), None), Ordering::Less);
+        assert_eq!(some_first(None, Some(1)), Ordering::Greater);
+        assert_eq!(some_first(Some(1), Some(2)), Ordering::Less);
+        assert_eq!(some_first::<usize>(None, None), Ordering::Equal);
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Context;
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+
+/// A small JSON file which persists things that we've learned between runs, so that
+/// we don't have to work everything out from scratch each time.
+#[derive(Debug)]
+pub struct StateStore {
+    path: PathBuf,
+    pub state: State,
+}
+
+/// The things we persist.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct State {
+    /// Milestones that we've synced, keyed by the milestone node ID.
+    #[serde(default)]
+    pub milestones: HashMap<String, MilestoneState>,
+    /// When the last successful sync of everything started.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_successful_sync: Option<time::OffsetDateTime>,
+    /// When the last successful full reconcile (ie a sync of everything that
+    /// wasn't limited to recently updated things) started.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_full_reconcile: Option<time::OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MilestoneState {
+    /// ID of the issue that mirrors the milestone.
+    pub issue_id: String,
+    /// A hash of everything we expected the issue and project items to look like
+    /// the last time they were synced. If this hasn't changed, there's nothing to do.
+    pub content_hash: String,
+}
+
+impl StateStore {
+    /// Load state from the given path, starting afresh if the file doesn't exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> Result<StateStore, anyhow::Error> {
+        let path = path.into();
+        let state = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Failed to decode state file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read state file {}", path.display())),
+        };
+        Ok(StateStore { path, state })
+    }
+
+    /// Write the state back to disk. We write to a temporary file and then move it into
+    /// place, so that a crash part way through doesn't leave a truncated file behind.
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let tmp_path = tmp_path_for(&self.path);
+        let bytes = serde_json::to_vec_pretty(&self.state)?;
+        std::fs::write(&tmp_path, bytes)
+            .with_context(|| format!("Failed to write state file {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to move state file into place at {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
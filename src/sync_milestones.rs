@@ -1,23 +1,47 @@
-use crate::api::{ Api, query::{self, project_details::{Projects, RoadmapProject, ToolsProject}, milestones::Milestone}, mutation, common::State };
+use crate::api::{ Api, query::{self, project_details::{Projects, RoadmapProject, ToolsProject, ProjectField, FieldDataType}, milestones::Milestone, project_repo::{self, ProjectIssue}}, common::{ State, FieldValue } };
+use crate::audit::{ AuditLog, Mutations, IssueFields, PreviousFieldValue };
+use crate::state_store::{ StateStore, MilestoneState };
+use crate::config::RemovalMode;
+use crate::lock::RunLock;
+use sha2::{ Sha256, Digest };
+use std::collections::HashMap;
 use tracing::{ info_span, warn, info };
 use regex::Regex;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug)]
 pub struct SyncMilestoneOpts<'a> {
     pub api: &'a Api,
     /// Details abotu the tools and roadmap project
     pub project_details: &'a Projects,
-    /// The org in which the repos we're talking about live.
-    pub org: &'a str,
+    /// The org in which the local issue repo and the projects live.
+    pub project_org: &'a str,
     /// Issues synced to the local project will be given whichever status has a name starting with this.
     pub local_project_milestone_status: &'a str,
+    /// What to do with the local project items of milestones that close.
+    pub milestone_removal: RemovalMode,
+    /// If the local project has a date field with this name, it's kept in sync with the milestone due date.
+    pub local_project_due_date_field: &'a str,
     /// Name of the repo that we'll create the issues in which are kept in sync with
     /// our milestones and are shown in the project boards.
     pub local_issue_repo_name: &'a str,
     /// Name of your team as it appears on the parity roadmap project.
     pub roadmap_team_name: &'a str,
-    /// A list of repos to find and sync milestones in.
-    pub repos_to_sync: &'a [String]
+    /// A list of repos (as `owner/name`) to find and sync milestones in.
+    pub repos_to_sync: &'a [String],
+    /// If set, only the milestone with this number is synced. This is used to sync
+    /// individual milestones in response to webhook events.
+    pub only_milestone_number: Option<usize>,
+    /// If set, only milestones updated since this time are synced, and any whose expected
+    /// content hasn't changed since they were last synced are skipped. If not set, every
+    /// milestone is checked and brought into sync.
+    pub updated_since: Option<time::OffsetDateTime>,
+    /// Remembers which issues and project items belong to which milestones, and
+    /// what they looked like when they were last synced.
+    pub state: &'a mut StateStore,
+    /// Every change we make is recorded here.
+    pub audit: &'a AuditLog,
+    /// Renewed before syncing each milestone.
+    pub lock: &'a RunLock
 }
 
 /// Sync milestones across our `repos_to_sync` to the project boards.
@@ -25,25 +49,51 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
     let SyncMilestoneOpts {
         api,
         project_details,
-        org,
+        project_org,
         local_project_milestone_status,
+        milestone_removal,
+        local_project_due_date_field,
         local_issue_repo_name,
         roadmap_team_name,
-        repos_to_sync
+        repos_to_sync,
+        only_milestone_number,
+        updated_since,
+        state,
+        audit,
+        lock
     } = opts;
 
+    let mutations = audit.mutations(api, "sync_milestones");
+
     let local_project_number = project_details.tools.number;
     let roadmap_project_number = project_details.roadmap.number;
+    let due_date_field = project_details.tools
+        .field(local_project_due_date_field)
+        .filter(|field| field.data_type == FieldDataType::Date);
 
-    // Details for the repo that will hold the issues that are kept in sync with milestones:
-    let project_repo = query::project_repo::run(&api, org, local_issue_repo_name, local_project_number, roadmap_project_number).await?;
     // All of the milestones found in target repositories:
-    let milestones_by_repo = query::milestones::run(&api, org, &repos_to_sync).await?;
+    let milestones_by_repo = query::milestones::run(api, repos_to_sync, updated_since).await?;
+    if milestones_by_repo.values().all(|milestones| milestones.is_empty()) {
+        return Ok(())
+    }
+
+    // Details for the repo that will hold the issues that are kept in sync with milestones:
+    let project_repo = query::project_repo::run(api, project_org, local_issue_repo_name, local_project_number, roadmap_project_number).await?;
+    // So that we can quickly look up issues we already know the IDs of:
+    let local_issue_repo = format!("{project_org}/{local_issue_repo_name}");
+    let project_repo_issues_by_id: HashMap<&str, &ProjectIssue> = project_repo.issues
+        .iter()
+        .map(|issue| (&*issue.id, issue))
+        .collect();
 
     // Look at each milestone (the last 100 most recently updated for every project, open or closed)
     // and make sure that the project boards and such are all in sync with them.
     for (repo, milestones) in &milestones_by_repo {
         for milestone in milestones {
+            if only_milestone_number.is_some_and(|n| n != milestone.number) {
+                continue
+            }
+
             let ms_state = milestone.state.to_string();
             let span = info_span!("sync_milestone", milestone.number, milestone.title, ms_state);
             let _ = span.enter();
@@ -51,7 +101,7 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
             let milestone_number = milestone.number;
             let milestone_title = &milestone.title;
             let milestone_body = milestone.description.trim_end_matches('\n');
-            let milestone_url = format!("https://github.com/{org}/{repo}/milestone/{milestone_number}");
+            let milestone_url = format!("https://github.com/{repo}/milestone/{milestone_number}");
 
             // A milestone should be on the public roadmap only if its title starts with "[public]":
             let is_milestone_public = milestone_title.to_ascii_lowercase().starts_with("[public]");
@@ -63,10 +113,15 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
             // The issue title we're expecting is either `[$repo] $title` normally, or if the milestone
             // is found in our local issue repo (eg subxt-team-milestones), just the title (because these
             // milestones are probably here to point at arbitrary repos or several places or whatever).
-            let expected_title = if repo == local_issue_repo_name {
+            // Repos in the same org as the projects are just given by name.
+            let expected_title = if repo.eq_ignore_ascii_case(&local_issue_repo) {
                 milestone_title
             } else {
-                format!("[{repo}] {milestone_title}")
+                let repo_name = repo
+                    .strip_prefix(project_org)
+                    .and_then(|name| name.strip_prefix('/'))
+                    .unwrap_or(repo);
+                format!("[{repo_name}] {milestone_title}")
             };
 
             // NOTE: Immediately after the URl we look for -->. Why? so that urls ending in eg /1 and /10
@@ -83,16 +138,56 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                 {milestone_url}\n\
             ");
             let expected_state = milestone.state;
+            let expected_due_date = milestone.due_on.as_ref().map(|due| due.time.date().to_string());
+
+            // If nothing that we'd sync has changed since we last synced this milestone, we can skip it
+            // (unless we're checking everything, in which case we want to fix anything that's drifted).
+            let content_hash = hash_expected_content(
+                project_details,
+                milestone,
+                &expected_title,
+                &expected_body,
+                is_milestone_public,
+                local_project_milestone_status,
+                due_date_field.and(expected_due_date.as_deref()),
+                roadmap_team_name
+            );
+            let known_milestone = state.state.milestones.get(&milestone.id);
+            if updated_since.is_some() && known_milestone.is_some_and(|m| m.content_hash == content_hash) {
+                continue
+            }
+            lock.renew(api).await?;
+
+            // If we've synced this milestone before then we know which issue it maps to. It may not be
+            // one of the issues we fetched above, in which case we fetch it directly.
+            let fetched_issue;
+            let known_issue = match known_milestone {
+                Some(known) => match project_repo_issues_by_id.get(&*known.issue_id) {
+                    Some(issue) => Some(*issue),
+                    None => {
+                        fetched_issue = project_repo::issues_by_id(
+                            api,
+                            std::slice::from_ref(&known.issue_id),
+                            local_project_number,
+                            roadmap_project_number
+                        ).await?.pop();
+                        fetched_issue.as_ref()
+                    }
+                },
+                None => None
+            };
 
-            // We match milestones to issues by looking for issues that link to the milestone.
+            // Else, we match milestones to issues by looking for issues that link to the milestone.
             // Why? Because we generate the links ourselves and the user can't change them by
             // editing the milestone (unlike the title or body).
-            let issue = project_repo
-                .issues
-                .iter()
-                .find(|issue| issue.description.contains(&expected_match_slug));
-
-            match issue {
+            let issue = known_issue.or_else(|| {
+                project_repo
+                    .issues
+                    .iter()
+                    .find(|issue| issue.description.contains(&expected_match_slug))
+            });
+
+            let issue_id = match issue {
                 // # There is an issue which lines up with the milestone already; make sure it's in sync!
                 Some(issue) => {
                     // Make sure that the issue text/description/state is in sync with the milestone:
@@ -102,29 +197,97 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
 
                     if update_title.is_some() || update_body.is_some() || update_state.is_some() {
                         info!("☑️  updating issue");
-                        mutation::update_issue::run(&api, &issue.id, update_title, update_body, update_state).await?;
+                        let previous = IssueFields {
+                            title: update_title.map(|_| issue.title.clone()),
+                            body: update_body.map(|_| issue.description.clone()),
+                            state: update_state.map(|_| issue.state),
+                        };
+                        let new = IssueFields {
+                            title: update_title.map(|t| t.to_owned()),
+                            body: update_body.map(|b| b.to_owned()),
+                            state: update_state,
+                        };
+                        mutations.update_issue("milestone changed", &issue.id, previous, new).await?;
                     }
 
                     match &issue.tools_project {
                         // ## there's already a tools project item; keep it in sync.
                         Some(tools_project) => {
-                            if milestone.state == State::CLOSED {
+                            if milestone.state == State::CLOSED && milestone_removal == RemovalMode::Archive {
+                                // ah but we closed the issue; archive it from our roadmap to keep it tidy.
+                                if !tools_project.is_archived {
+                                    info!("📥 archiving on local roadmap");
+                                    mutations.archive_item(
+                                        "milestone closed",
+                                        &project_details.tools.id,
+                                        &tools_project.item_id
+                                    ).await?;
+                                }
+                            } else if milestone.state == State::CLOSED {
                                 // ah but we closed the issue; remove it from our roadmap to keep it tidy.
                                 info!("❌ removing from local roadmap");
-                                mutation::remove_item_from_project::run(&api, &project_details.tools.id, &tools_project.item_id).await?;
+                                let previous_status = tools_project.status_id
+                                    .iter()
+                                    .map(|status_id| PreviousFieldValue { field_id: project_details.tools.status.id.clone(), value: FieldValue::SingleSelect(status_id.clone()) });
+                                let previous_dates = tools_project.dates
+                                    .iter()
+                                    .map(|(field_id, date)| PreviousFieldValue { field_id: field_id.clone(), value: FieldValue::Date(date.clone()) });
+                                let previous_values = previous_status.chain(previous_dates).collect();
+                                mutations.remove_item(
+                                    "milestone closed",
+                                    &project_details.tools.id,
+                                    &tools_project.item_id,
+                                    Some(&issue.id),
+                                    previous_values
+                                ).await?;
                             } else {
+                                if tools_project.is_archived {
+                                    info!("📤 unarchiving on local roadmap");
+                                    mutations.unarchive_item(
+                                        "milestone is open",
+                                        &project_details.tools.id,
+                                        &tools_project.item_id
+                                    ).await?;
+                                }
+
                                 let expected_status_id = get_tools_project_status_id(&project_details.tools, local_project_milestone_status)?;
                                 let do_update_status = tools_project.status_id.as_deref() != Some(expected_status_id);
                                 if do_update_status {
                                     info!("☑️  updating local project status");
-                                    mutation::update_item_field_in_project::run(
-                                        &api,
+                                    mutations.set_item_option(
+                                        "milestone status changed",
                                         &project_details.tools.id,
                                         &tools_project.item_id,
                                         &project_details.tools.status.id,
+                                        tools_project.status_id.as_deref(),
                                         expected_status_id
                                     ).await?;
                                 }
+
+                                if let Some(field) = due_date_field {
+                                    let current_due_date = tools_project.dates.get(&field.id);
+                                    if current_due_date != expected_due_date.as_ref() {
+                                        info!("☑️  updating local project due date");
+                                        let previous_value = current_due_date.cloned().map(FieldValue::Date);
+                                        match &expected_due_date {
+                                            Some(date) => mutations.set_item_field(
+                                                "milestone due date changed",
+                                                &project_details.tools.id,
+                                                &tools_project.item_id,
+                                                &field.id,
+                                                previous_value,
+                                                FieldValue::Date(date.clone())
+                                            ).await?,
+                                            None => mutations.clear_item_field(
+                                                "milestone due date removed",
+                                                &project_details.tools.id,
+                                                &tools_project.item_id,
+                                                &field.id,
+                                                previous_value
+                                            ).await?,
+                                        }
+                                    }
+                                }
                             }
 
                         },
@@ -133,10 +296,11 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                             if milestone.state == State::OPEN {
                                 info!("✅ creating issue");
                                 add_tools_project_item(
-                                    &api,
+                                    mutations,
                                     &issue.id,
                                     &project_details.tools,
-                                    local_project_milestone_status
+                                    local_project_milestone_status,
+                                    due_date_field.zip(expected_due_date.as_deref())
                                 ).await?;
                             }
                         }
@@ -148,18 +312,25 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                             if !is_milestone_public {
                                 // ah but we don't want it to be public now, so remove it from the roadmap entirely.
                                 info!("❌ removing from public roadmap");
-                                mutation::remove_item_from_project::run(&api, &project_details.roadmap.id, &roadmap_project.item_id).await?;
+                                mutations.remove_item(
+                                    "milestone is not public",
+                                    &project_details.roadmap.id,
+                                    &roadmap_project.item_id,
+                                    Some(&issue.id),
+                                    roadmap_previous_values(&project_details.roadmap, roadmap_project)
+                                ).await?;
                             } else {
                                 // sync status
                                 let expected_status_id = get_roadmap_project_state_id(&project_details.roadmap, expected_state)?;
                                 let do_update_status = roadmap_project.status_id.as_deref() != Some(expected_status_id);
                                 if do_update_status {
                                     info!("☑️  updating public roadmap item status");
-                                    mutation::update_item_field_in_project::run(
-                                        &api,
+                                    mutations.set_item_option(
+                                        "milestone state changed",
                                         &project_details.roadmap.id,
                                         &roadmap_project.item_id,
                                         &project_details.roadmap.status.id,
+                                        roadmap_project.status_id.as_deref(),
                                         expected_status_id
                                     ).await?;
                                 }
@@ -169,11 +340,12 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                                 let do_update_team = roadmap_project.team_id.as_deref() != Some(expected_team_id);
                                 if do_update_team {
                                     info!("☑️  updating public roadmap item team");
-                                    mutation::update_item_field_in_project::run(
-                                        &api,
+                                    mutations.set_item_option(
+                                        "roadmap team changed",
                                         &project_details.roadmap.id,
                                         &roadmap_project.item_id,
                                         &project_details.roadmap.team.id,
+                                        roadmap_project.team_id.as_deref(),
                                         expected_team_id
                                     ).await?;
                                 }
@@ -186,14 +358,16 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                                     .as_ref()
                                     .and_then(|due| try_get_matching_roadmap_deadline(&project_details.roadmap, &due.time));
 
-                                if expected_deadline == None && milestone.state == State::CLOSED {
+                                if expected_deadline.is_none() && milestone.state == State::CLOSED {
                                     // no matching deadline column (or no set deadline), and the milestone is closed,
                                     // so it's time to just remove it from the roadmap entirely.
                                     info!("❌ removing old closed milestone from public roadmap");
-                                    mutation::remove_item_from_project::run(
-                                        &api,
+                                    mutations.remove_item(
+                                        "milestone closed and has no deadline on the roadmap",
                                         &project_details.roadmap.id,
-                                        &roadmap_project.item_id
+                                        &roadmap_project.item_id,
+                                        Some(&issue.id),
+                                        roadmap_previous_values(&project_details.roadmap, roadmap_project)
                                     ).await?;
                                 } else if roadmap_project.deadline_id.as_deref() != expected_deadline {
                                     // deadlines differ between milestone and roadmap project item...
@@ -202,11 +376,12 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                                             // Some deadline is set but it's different from the one on the roadmap,
                                             // so sync the deadline to the roadmap.
                                             info!("☑️  updating public roadmap item deadline");
-                                            mutation::update_item_field_in_project::run(
-                                                &api,
+                                            mutations.set_item_option(
+                                                "milestone due date changed",
                                                 &project_details.roadmap.id,
                                                 &roadmap_project.item_id,
                                                 &project_details.roadmap.deadline.id,
+                                                roadmap_project.deadline_id.as_deref(),
                                                 deadline
                                             ).await?;
                                         },
@@ -214,11 +389,12 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                                             // no matching deadline column (or no set deadline), but project item has a
                                             // deadline on the roadmap, so remove said roadmap deadline to sync
                                             warn!("🛑 milestone due date not found on roadmap, but it's still open");
-                                            mutation::clear_item_field_in_project::run(
-                                                &api,
+                                            mutations.clear_item_field(
+                                                "milestone due date not found on roadmap",
                                                 &project_details.roadmap.id,
                                                 &roadmap_project.item_id,
                                                 &project_details.roadmap.deadline.id,
+                                                roadmap_project.deadline_id.clone().map(FieldValue::SingleSelect),
                                             ).await?;
                                         }
                                     }
@@ -230,15 +406,17 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                             if is_milestone_public && milestone.state == State::OPEN {
                                 info!("✅ adding to public roadmap");
                                 add_roadmap_project_item(
-                                    &api,
+                                    mutations,
                                     &issue.id,
-                                    &milestone,
+                                    milestone,
                                     &project_details.roadmap,
                                     roadmap_team_name
                                 ).await?;
                             }
                         }
                     }
+
+                    issue.id.clone()
                 },
                 // # There is not a corresponding issue. Create new issue and assign it to projects as needed.
                 None => {
@@ -251,8 +429,8 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
 
                     // Create an issue:
                     info!("✅ creating issue");
-                    let issue_id = mutation::create_issue::run(
-                        &api,
+                    let issue_id = mutations.create_issue(
+                        "new milestone",
                         &project_repo.id,
                         &expected_title,
                         &expected_body
@@ -261,87 +439,113 @@ pub async fn sync_milestones(opts: SyncMilestoneOpts<'_>) -> Result<(), anyhow::
                     // Add the issue to our tools project
                     info!("✅ creating local project item");
                     add_tools_project_item(
-                        &api,
+                        mutations,
                         &issue_id,
                         &project_details.tools,
-                        local_project_milestone_status
+                        local_project_milestone_status,
+                        due_date_field.zip(expected_due_date.as_deref())
                     ).await?;
 
                     // If the milestone is tagged [public], add it to the roadmap too.
                     if is_milestone_public {
                         info!("✅ creating roadmap project item");
                         add_roadmap_project_item(
-                            &api,
+                            mutations,
                             &issue_id,
-                            &milestone,
+                            milestone,
                             &project_details.roadmap,
                             roadmap_team_name
                         ).await?;
                     }
+
+                    issue_id
                 }
-            }
+            };
 
+            // Remember what we synced this milestone to.
+            state.state.milestones.insert(milestone.id.clone(), MilestoneState {
+                issue_id,
+                content_hash
+            });
         }
     }
 
     Ok(())
 }
 
-async fn add_tools_project_item(api: &Api, issue_id: &str, tools_project: &ToolsProject, milestone_status_name: &str) -> Result<(), anyhow::Error> {
-    let tools_item_id = mutation::add_item_to_project::run(&api, &issue_id, &tools_project.id).await?;
-    mutation::update_item_field_in_project::run(
-        &api,
+/// Add an issue to the tools project. If a due date field and value are given, that's set too.
+async fn add_tools_project_item(mutations: Mutations<'_>, issue_id: &str, tools_project: &ToolsProject, milestone_status_name: &str, due_date: Option<(&ProjectField, &str)>) -> Result<(), anyhow::Error> {
+    let reason = "milestone is open";
+    let tools_item_id = mutations.add_item(reason, &tools_project.id, issue_id).await?;
+    mutations.set_item_option(
+        reason,
         &tools_project.id,
         &tools_item_id,
         &tools_project.status.id,
-        get_tools_project_status_id(&tools_project, milestone_status_name)?
+        None,
+        get_tools_project_status_id(tools_project, milestone_status_name)?
     ).await?;
+    if let Some((field, date)) = due_date {
+        mutations.set_item_field(
+            reason,
+            &tools_project.id,
+            &tools_item_id,
+            &field.id,
+            None,
+            FieldValue::Date(date.to_owned())
+        ).await?;
+    }
     Ok(())
 }
 
-async fn add_roadmap_project_item(api: &Api, issue_id: &str, milestone: &Milestone, roadmap_project: &RoadmapProject, roadmap_team_name: &str) -> Result<(), anyhow::Error> {
-    let roadmap_item_id = mutation::add_item_to_project::run(&api, &issue_id, &roadmap_project.id).await?;
+async fn add_roadmap_project_item(mutations: Mutations<'_>, issue_id: &str, milestone: &Milestone, roadmap_project: &RoadmapProject, roadmap_team_name: &str) -> Result<(), anyhow::Error> {
+    let reason = "milestone is public";
+    let roadmap_item_id = mutations.add_item(reason, &roadmap_project.id, issue_id).await?;
 
     // Status (Open or Closed as per the milestone)
-    mutation::update_item_field_in_project::run(
-        &api,
+    mutations.set_item_option(
+        reason,
         &roadmap_project.id,
         &roadmap_item_id,
         &roadmap_project.status.id,
-        get_roadmap_project_state_id(&roadmap_project, milestone.state)?
+        None,
+        get_roadmap_project_state_id(roadmap_project, milestone.state)?
     ).await?;
 
     // Team (Tools, or as configured above)
-    mutation::update_item_field_in_project::run(
-        &api,
+    mutations.set_item_option(
+        reason,
         &roadmap_project.id,
         &roadmap_item_id,
         &roadmap_project.team.id,
-        get_roadmap_project_team_id(&roadmap_project, roadmap_team_name)?
+        None,
+        get_roadmap_project_team_id(roadmap_project, roadmap_team_name)?
     ).await?;
 
     // Column for due date (match it up to the milestone due date, remove if no due date or no matching column).
     let due_field_id = milestone
         .due_on
         .as_ref()
-        .and_then(|due| try_get_matching_roadmap_deadline(&roadmap_project, &due.time));
+        .and_then(|due| try_get_matching_roadmap_deadline(roadmap_project, &due.time));
     match due_field_id {
         Some(due_field_id) => {
-            mutation::update_item_field_in_project::run(
-                &api,
+            mutations.set_item_option(
+                reason,
                 &roadmap_project.id,
                 &roadmap_item_id,
                 &roadmap_project.deadline.id,
+                None,
                 due_field_id
             ).await?;
         },
         None => {
             warn!("🛑 milestone due date not found on roadmap");
-            mutation::clear_item_field_in_project::run(
-                &api,
+            mutations.clear_item_field(
+                reason,
                 &roadmap_project.id,
                 &roadmap_item_id,
                 &roadmap_project.deadline.id,
+                None
             ).await?;
         }
     }
@@ -349,6 +553,48 @@ async fn add_roadmap_project_item(api: &Api, issue_id: &str, milestone: &Milesto
     Ok(())
 }
 
+/// The field values on a roadmap item, so that we can put them back if need be.
+fn roadmap_previous_values(details: &RoadmapProject, item: &project_repo::RoadmapProject) -> Vec<PreviousFieldValue> {
+    [
+        (&details.status.id, &item.status_id),
+        (&details.team.id, &item.team_id),
+        (&details.deadline.id, &item.deadline_id),
+    ]
+    .into_iter()
+    .filter_map(|(field_id, value)| {
+        value.as_ref().map(|value| PreviousFieldValue { field_id: field_id.clone(), value: FieldValue::SingleSelect(value.clone()) })
+    })
+    .collect()
+}
+
+/// Hash everything that we'd sync to the issue and project items for some milestone,
+/// so that we can tell whether anything has changed since it was last synced.
+#[allow(clippy::too_many_arguments)]
+fn hash_expected_content(
+    project_details: &Projects,
+    milestone: &Milestone,
+    expected_title: &str,
+    expected_body: &str,
+    is_milestone_public: bool,
+    local_project_milestone_status: &str,
+    local_project_due_date: Option<&str>,
+    roadmap_team_name: &str
+) -> String {
+    let expected = (
+        expected_title,
+        expected_body,
+        milestone.state,
+        is_milestone_public,
+        get_tools_project_status_id(&project_details.tools, local_project_milestone_status).ok(),
+        local_project_due_date,
+        get_roadmap_project_state_id(&project_details.roadmap, milestone.state).ok(),
+        get_roadmap_project_team_id(&project_details.roadmap, roadmap_team_name).ok(),
+        milestone.due_on.as_ref().and_then(|due| try_get_matching_roadmap_deadline(&project_details.roadmap, &due.time)),
+    );
+    let bytes = serde_json::to_vec(&expected).expect("tuple of strings and bools can be serialized");
+    hex::encode(Sha256::digest(bytes))
+}
+
 fn get_tools_project_status_id<'a>(details: &'a query::project_details::ToolsProject, milestone_status_name: &str) -> Result<&'a str, anyhow::Error> {
     details.status.options
         .iter()
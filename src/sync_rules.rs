@@ -0,0 +1,346 @@
+use crate::api::{ Api, common::FieldValue, query::{ self, project_details::{ ProjectField, ToolsProject }, project_items::ProjectItem } };
+use anyhow::Context;
+use crate::utils::{ self, Placement, SyncScope };
+use crate::rules::{ Match, Rule, RuleFieldValue, Sources, SourceOpts, Vars };
+use crate::audit::AuditLog;
+use crate::lock::RunLock;
+use crate::config::{ Config, RemovalMode };
+use crate::sort_columns::{ sort_columns, SortColumnsOpts };
+use std::collections::HashMap;
+use tracing::{ info, info_span, warn };
+
+pub struct SyncRulesOpts<'a> {
+    pub api: &'a Api,
+    /// The org that the projects live in.
+    pub project_org: &'a str,
+    /// The orgs to look for issues and PRs in, the first being our default org.
+    pub orgs: &'a [String],
+    /// The rules to sync, and other settings for them.
+    pub config: &'a Config,
+    pub local_project_details: &'a ToolsProject,
+    /// The repo (as `owner/name`) that milestone issues are created in.
+    pub local_issue_repo: &'a str,
+    pub team_group_name: &'a str,
+    pub team_members: &'a [String],
+    pub team_repos: &'a [String],
+    pub scope: SyncScope<'a>,
+    pub audit: &'a AuditLog,
+    /// Renewed after looking for things and before changing each project.
+    pub lock: &'a RunLock
+}
+
+/// Find everything that matches each rule, and put it in the right column of the right project.
+pub async fn sync_rules(opts: SyncRulesOpts<'_>) -> Result<(), anyhow::Error> {
+    let SyncRulesOpts {
+        api,
+        project_org,
+        orgs,
+        config,
+        local_project_details,
+        local_issue_repo,
+        team_group_name,
+        team_members,
+        team_repos,
+        scope,
+        audit,
+        lock
+    } = opts;
+
+    let span = info_span!("sync_rules");
+    let _ = span.enter();
+
+    let rules = &config.rules;
+    let sources = Sources::new(SourceOpts {
+        api,
+        orgs,
+        team_members,
+        team_repos,
+        team_group_name,
+        local_issue_repo,
+        code_owner_repos: &config.code_owner_repos,
+        updated_since: scope.updated_since(),
+    });
+
+    // Find the issues and PRs matching each rule:
+    let mut matches: Vec<(&Rule, Vec<Match>)> = Vec::new();
+    for rule in rules {
+        let found = sources.find(&rule.source).await?;
+        matches.push((rule, found));
+    }
+
+    // Drop anything that's excluded, either from every rule or from particular ones. We need
+    // to look up the details of everything that was found to know, so only do so if need be.
+    let mut summaries = HashMap::new();
+    if !config.exclude.is_empty() || rules.iter().any(|rule| !rule.exclude.is_empty()) {
+        let mut ids: Vec<&str> = matches.iter().flat_map(|(_, found)| found.iter().map(|m| &*m.id)).collect();
+        ids.sort();
+        ids.dedup();
+        summaries = query::content_summaries::run(api, &ids).await?;
+        for (rule, found) in &mut matches {
+            found.retain(|m| match summaries.get(&m.id) {
+                Some(summary) => !config.exclude.excludes(&orgs[0], summary) && !rule.exclude.excludes(&orgs[0], summary),
+                None => true
+            });
+        }
+    }
+
+    for (rule, found) in &matches {
+        info!("rule `{}` matches {} issues/PRs", rule.name, found.len());
+    }
+
+    let project_number_for = |rule: &Rule| rule.project.unwrap_or(local_project_details.number);
+    let mut project_numbers: Vec<usize> = rules.iter().map(project_number_for).collect();
+    project_numbers.sort();
+    project_numbers.dedup();
+
+    for project_number in project_numbers {
+        let other_project_details;
+        let project_details = if project_number == local_project_details.number {
+            local_project_details
+        } else {
+            other_project_details = query::project_details::tools_project(api, project_org, project_number).await?;
+            &other_project_details
+        };
+
+        let project_matches: Vec<_> = matches
+            .iter()
+            .filter(|(rule, _)| project_number_for(rule) == project_number)
+            .collect();
+
+        // Every column that some rule puts things in is ours to manage, even if
+        // nothing currently matches the rule.
+        let mut managed_statuses: Vec<&str> = project_matches.iter().map(|(rule, _)| &*rule.status).collect();
+        // Columns that stale items are moved to are ours to manage too.
+        managed_statuses.extend(project_matches.iter().filter_map(|(rule, _)| {
+            config.column(&rule.status)?.stale.as_ref()?.move_to.as_deref()
+        }));
+        managed_statuses.sort();
+        managed_statuses.dedup();
+        let archived_statuses: Vec<&str> = managed_statuses
+            .iter()
+            .copied()
+            .filter(|status| config.removal_mode(status) == RemovalMode::Archive)
+            .collect();
+
+        // The project fields that each rule sets:
+        let mut rule_fields: Vec<Vec<&ProjectField>> = Vec::new();
+        for (rule, _) in &project_matches {
+            let mut fields = Vec::new();
+            for field_name in rule.fields.keys() {
+                let field = project_details
+                    .field(field_name)
+                    .ok_or_else(|| anyhow::anyhow!("Rule `{}`: no field called '{field_name}' in project {project_number}", rule.name))?;
+                fields.push(field);
+            }
+            rule_fields.push(fields);
+        }
+
+        // Work out where each issue or PR should go, preferring higher priority rules.
+        let mut winning_rules: HashMap<&str, (usize, &Match)> = HashMap::new();
+        for (idx, (rule, found)) in project_matches.iter().enumerate() {
+            for m in found {
+                let current = winning_rules.entry(&m.id).or_insert((idx, m));
+                if rule.priority > project_matches[current.0].0.priority {
+                    *current = (idx, m);
+                }
+            }
+        }
+
+        // Field values can depend on what was found, so they're worked out for each issue or PR.
+        // Each field is only set once: fields that rules set take precedence over those set from
+        // labels, which take precedence over the stale field.
+        let mut item_fields: HashMap<&str, Vec<(String, Option<FieldValue>)>> = HashMap::new();
+        for (&id, &(idx, m)) in &winning_rules {
+            let rule = project_matches[idx].0;
+            let mut fields = Vec::new();
+            for field in &rule_fields[idx] {
+                let value = rule.fields[&field.name]
+                    .to_field_value(field, &m.vars)
+                    .with_context(|| format!("Rule `{}`: invalid value for field '{}'", rule.name, field.name))?;
+                push_field(&mut fields, &field.id, value);
+            }
+            item_fields.insert(id, fields);
+        }
+
+        let items = query::project_items::run(api, project_org, project_details.number).await?;
+        let items_by_content_id: HashMap<&str, &ProjectItem> = items
+            .iter()
+            .filter_map(|item| Some((item.content_id.as_deref()?, item)))
+            .collect();
+
+        // Keep label driven fields in sync. Things which are already on the board come with their
+        // labels, and we look up the labels of anything else (unless we already have) so that
+        // new items get these fields as they're added.
+        if !config.label_fields.is_empty() {
+            let missing_ids: Vec<&str> = winning_rules
+                .keys()
+                .copied()
+                .filter(|id| !items_by_content_id.contains_key(id) && !summaries.contains_key(*id))
+                .collect();
+            if !missing_ids.is_empty() {
+                summaries.extend(query::content_summaries::run(api, &missing_ids).await?);
+            }
+        }
+        for label_field in &config.label_fields {
+            let Some(field) = project_details.field(&label_field.field) else {
+                warn!("🛑 no field called '{}' in project {project_number} to set from labels", label_field.field);
+                continue
+            };
+            for &id in winning_rules.keys() {
+                let labels = match items_by_content_id.get(id).and_then(|item| item.content_details()) {
+                    Some(details) => &details.labels,
+                    None => match summaries.get(id) {
+                        Some(summary) => &summary.labels,
+                        None => continue
+                    }
+                };
+                let value = match label_field.option_for(labels) {
+                    Some(option) => match RuleFieldValue::Text(option.to_owned()).to_field_value(field, &Vars::new()) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            warn!("🛑 can't set '{}' to '{option}' from labels in project {project_number}: {e:#}", label_field.field);
+                            continue
+                        }
+                    },
+                    None => None
+                };
+                push_field(item_fields.get_mut(id).expect("every winning rule has fields"), &field.id, value);
+            }
+        }
+
+        // Look for things which have been sitting in a column for too long, moving them
+        // and setting fields on them if configured to. Incremental syncs only find things which
+        // have been updated recently (which stale things haven't been), so we also look at the
+        // items in our columns that this sync would otherwise leave alone.
+        let status_names: HashMap<&str, &str> = project_details.status.options
+            .iter()
+            .map(|option| (&*option.id, &*option.name))
+            .collect();
+        let mut stale_candidates: Vec<(&str, &str)> = winning_rules
+            .iter()
+            .map(|(&id, &(idx, _))| (id, &*project_matches[idx].0.status))
+            .collect();
+        for item in &items {
+            let Some(id) = item.content_id.as_deref() else {
+                continue
+            };
+            if item.is_archived || winning_rules.contains_key(id) || !scope.includes_content_id(id) || scope.includes_item(item) {
+                continue
+            }
+            let status = item.status_field_value_id.as_deref().and_then(|option_id| status_names.get(option_id));
+            if let Some(&status) = status.filter(|status| managed_statuses.contains(status)) {
+                stale_candidates.push((id, status));
+            }
+        }
+
+        let now = time::OffsetDateTime::now_utc();
+        let mut stale_statuses: HashMap<&str, &str> = HashMap::new();
+        let mut stale_count = 0;
+        for &(id, status) in &stale_candidates {
+            let Some(stale) = config.column(status).and_then(|c| c.stale.as_ref()) else {
+                continue
+            };
+            // Things that aren't on the board yet can't be stale.
+            let last_activity_at = items_by_content_id
+                .get(id)
+                .and_then(|item| item.content_details())
+                .filter(|details| (now - details.last_activity_at).whole_days() >= stale.days_for(&details.labels))
+                .map(|details| {
+                    warn!("🕸️  {} in `{status}` is stale (no activity since {})", items_by_content_id[id].describe(), details.last_activity_at.date());
+                    stale_count += 1;
+                    details.last_activity_at
+                });
+            // Things that no rule looked at are only touched if they're stale.
+            if last_activity_at.is_none() && !winning_rules.contains_key(id) {
+                continue
+            }
+            let fields = item_fields.entry(id).or_default();
+
+            if let (Some(_), Some(move_to)) = (last_activity_at, &stale.move_to) {
+                stale_statuses.insert(id, move_to);
+            }
+            if let Some(field_name) = &stale.field {
+                let field = project_details
+                    .field(field_name)
+                    .ok_or_else(|| anyhow::anyhow!("Column `{status}`: no stale field called '{field_name}' in project {project_number}"))?;
+                let value = match last_activity_at {
+                    Some(at) => RuleFieldValue::Text(at.date().to_string())
+                        .to_field_value(field, &Vars::new())
+                        .with_context(|| format!("Column `{status}`: stale field '{field_name}' should be a text or date field"))?,
+                    None => None
+                };
+                push_field(fields, &field.id, value);
+            }
+        }
+        if stale_count > 0 {
+            info!("🕸️  {stale_count} stale items in project {project_number} ({} to be moved)", stale_statuses.len());
+        }
+
+        let mut placements: HashMap<String, Placement> = winning_rules
+            .iter()
+            .map(|(&id, &(idx, _))| {
+                let rule = project_matches[idx].0;
+                let status = stale_statuses.get(id).copied().unwrap_or(&rule.status);
+                (id.to_owned(), Placement { status, reason: &rule.name, fields: &item_fields[id] })
+            })
+            .collect();
+        // Stale things that no rule looked at stay in their column unless they're to be moved.
+        for &(id, status) in &stale_candidates {
+            if let (false, Some(fields)) = (winning_rules.contains_key(id), item_fields.get(id)) {
+                let status = stale_statuses.get(id).copied().unwrap_or(status);
+                placements.insert(id.to_owned(), Placement { status, reason: "stale", fields });
+            }
+        }
+
+        lock.renew(api).await?;
+        utils::sync_issues_to_project(utils::SyncIssuesToProjectOpts {
+            api,
+            project_details,
+            items: &items,
+            managed_statuses: &managed_statuses,
+            placements: &placements,
+            archived_statuses: &archived_statuses,
+            remove_inaccessible_items: config.remove_inaccessible_items,
+            scope,
+            audit,
+            stage: "sync_rules"
+        }).await?;
+
+        // Syncing particular issues and PRs can only change the order of the columns that
+        // they were or now are in.
+        let sort_statuses: Vec<&str> = match scope {
+            SyncScope::Content { .. } => managed_statuses
+                .iter()
+                .copied()
+                .filter(|&status| {
+                    placements.iter().any(|(id, p)| p.status == status && scope.includes_content_id(id))
+                        || items.iter().any(|item| {
+                            scope.includes_item(item)
+                                && item.status_field_value_id.as_deref().and_then(|option_id| status_names.get(option_id)) == Some(&status)
+                        })
+                })
+                .collect(),
+            SyncScope::All | SyncScope::UpdatedSince { .. } => managed_statuses.clone(),
+        };
+
+        lock.renew(api).await?;
+        sort_columns(SortColumnsOpts {
+            api,
+            org: project_org,
+            config,
+            project_details,
+            statuses: &sort_statuses,
+            audit,
+            stage: "sync_rules"
+        }).await?;
+    }
+
+    Ok(())
+}
+
+/// Add a field value to the given list of fields, unless a value for that field is already in it.
+fn push_field(fields: &mut Vec<(String, Option<FieldValue>)>, field_id: &str, value: Option<FieldValue>) {
+    if !fields.iter().any(|(id, _)| id == field_id) {
+        fields.push((field_id.to_owned(), value));
+    }
+}
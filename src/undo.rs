@@ -0,0 +1,150 @@
+use crate::api::{ Api, common::State };
+use crate::audit::{ self, AuditLog, AuditAction, IssueFields };
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{ info, warn };
+
+/// Undo every change recorded in the audit log for the given run, most recent change first.
+/// The changes we make to do this are themselves recorded in the audit log.
+pub async fn undo_run(api: &Api, audit: &AuditLog, audit_log_path: &Path, run_id: &str) -> Result<(), anyhow::Error> {
+    let entries: Vec<_> = audit::read_entries(audit_log_path)?
+        .into_iter()
+        .filter(|entry| entry.run_id == run_id)
+        .collect();
+
+    if entries.is_empty() {
+        anyhow::bail!("No changes were recorded for run '{run_id}' in {}", audit_log_path.display());
+    }
+
+    info!("↩️  undoing {} changes from run {run_id}", entries.len());
+    let mutations = audit.mutations(api, "undo");
+    let reason = format!("undoing run {run_id}");
+
+    let mut item_ids = ItemIds::default();
+
+    for entry in entries.iter().rev() {
+        match &entry.action {
+            AuditAction::CreateIssue { issue_id, title, .. } => {
+                // We can't delete issues, so the best we can do is close them.
+                info!("↩️  closing created issue '{title}'");
+                mutations.update_issue(
+                    &reason,
+                    issue_id,
+                    IssueFields { state: Some(State::OPEN), ..Default::default() },
+                    IssueFields { state: Some(State::CLOSED), ..Default::default() }
+                ).await?;
+            },
+            AuditAction::UpdateIssue { issue_id, previous, new } => {
+                info!("↩️  restoring issue {issue_id}");
+                mutations.update_issue(&reason, issue_id, new.clone(), previous.clone()).await?;
+            },
+            AuditAction::AddItem { project_id, content_id, item_id } => {
+                let item_id = item_ids.current(item_id);
+                info!("↩️  removing added item {item_id}");
+                mutations.remove_item(&reason, project_id, &item_id, Some(content_id), Vec::new()).await?;
+            },
+            AuditAction::RemoveItem { project_id, item_id, content_id, previous_values } => {
+                let Some(content_id) = content_id else {
+                    warn!("🛑 can't re-add removed item {item_id}; its content was inaccessible");
+                    continue
+                };
+                info!("↩️  re-adding removed item {item_id}");
+                let new_item_id = mutations.add_item(&reason, project_id, content_id).await?;
+                for previous in previous_values {
+                    mutations.set_item_field(&reason, project_id, &new_item_id, &previous.field_id, None, previous.value.clone()).await?;
+                }
+                item_ids.replace(item_id, new_item_id);
+            },
+            AuditAction::ArchiveItem { project_id, item_id } => {
+                let item_id = item_ids.current(item_id);
+                info!("↩️  unarchiving item {item_id}");
+                mutations.unarchive_item(&reason, project_id, &item_id).await?;
+            },
+            AuditAction::UnarchiveItem { project_id, item_id } => {
+                let item_id = item_ids.current(item_id);
+                info!("↩️  re-archiving item {item_id}");
+                mutations.archive_item(&reason, project_id, &item_id).await?;
+            },
+            AuditAction::MoveItem { project_id, item_id, previous_after_id, after_id } => {
+                let item_id = item_ids.current(item_id);
+                let previous_after_id = previous_after_id.as_ref().map(|id| item_ids.current(id));
+                let after_id = after_id.as_ref().map(|id| item_ids.current(id));
+                info!("↩️  moving item {item_id} back");
+                mutations.move_item(&reason, project_id, &item_id, after_id.as_deref(), previous_after_id.as_deref()).await?;
+            },
+            AuditAction::SetItemField { project_id, item_id, field_id, previous_value, value } => {
+                let item_id = item_ids.current(item_id);
+                info!("↩️  restoring field {field_id} on item {item_id}");
+                match previous_value {
+                    Some(previous_value) => {
+                        mutations.set_item_field(&reason, project_id, &item_id, field_id, Some(value.clone()), previous_value.clone()).await?;
+                    },
+                    None => {
+                        mutations.clear_item_field(&reason, project_id, &item_id, field_id, Some(value.clone())).await?;
+                    }
+                }
+            },
+            AuditAction::ClearItemField { project_id, item_id, field_id, previous_value } => {
+                // Nothing to restore if the field wasn't set before.
+                let Some(previous_value) = previous_value else { continue };
+                let item_id = item_ids.current(item_id);
+                info!("↩️  restoring field {field_id} on item {item_id}");
+                mutations.set_item_field(&reason, project_id, &item_id, field_id, None, previous_value.clone()).await?;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Items that we add back to a project have new IDs, so we keep track of them in case
+/// earlier changes to the same items also need undoing.
+#[derive(Debug, Default)]
+struct ItemIds {
+    new_ids: HashMap<String, String>,
+}
+
+impl ItemIds {
+    /// The current ID of the item which had the given ID when some change was made.
+    fn current(&self, item_id: &str) -> String {
+        let mut item_id = item_id;
+        while let Some(new_id) = self.new_ids.get(item_id) {
+            item_id = new_id;
+        }
+        item_id.to_owned()
+    }
+
+    /// Note that the item with the given ID has been added back with a new one.
+    fn replace(&mut self, old_id: &str, new_id: String) {
+        self.new_ids.insert(old_id.to_owned(), new_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_items_keep_their_ids() {
+        let item_ids = ItemIds::default();
+        assert_eq!(item_ids.current("PVTI_1"), "PVTI_1");
+    }
+
+    #[test]
+    fn re_added_items_use_their_new_ids() {
+        let mut item_ids = ItemIds::default();
+        item_ids.replace("PVTI_1", "PVTI_2".to_owned());
+        assert_eq!(item_ids.current("PVTI_1"), "PVTI_2");
+        assert_eq!(item_ids.current("PVTI_2"), "PVTI_2");
+        assert_eq!(item_ids.current("PVTI_3"), "PVTI_3");
+    }
+
+    #[test]
+    fn items_re_added_several_times_use_their_latest_ids() {
+        let mut item_ids = ItemIds::default();
+        item_ids.replace("PVTI_1", "PVTI_2".to_owned());
+        item_ids.replace("PVTI_2", "PVTI_3".to_owned());
+        assert_eq!(item_ids.current("PVTI_1"), "PVTI_3");
+        assert_eq!(item_ids.current("PVTI_2"), "PVTI_3");
+    }
+}
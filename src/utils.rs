@@ -1,78 +1,353 @@
-use crate::api::{ Api, query::{ self, project_details::ToolsProject }, mutation };
-use tracing::info;
+use crate::api::{ Api, common::FieldValue, query::{ project_details::ToolsProject, project_items::ProjectItem } };
+use crate::audit::{ AuditLog, PreviousFieldValue };
+use std::collections::HashMap;
+use tracing::{ info, warn };
 
 /// Options for `sync_issues_to_project`
 pub struct SyncIssuesToProjectOpts<'a> {
     pub api: &'a Api,
-    pub org: &'a str,
-    pub field_status_value_name: &'a str,
     pub project_details: &'a ToolsProject,
-    pub issue_ids: &'a [String],
+    /// Every item currently in the project.
+    pub items: &'a [ProjectItem],
+    /// The status columns that we're in charge of. Items in these columns which
+    /// aren't in `placements` are removed from the project.
+    pub managed_statuses: &'a [&'a str],
+    /// The issues and PRs that should be in the managed columns, keyed by their ID.
+    pub placements: &'a HashMap<String, Placement<'a>>,
+    /// Items which no longer belong in these managed status columns are archived
+    /// rather than deleted.
+    pub archived_statuses: &'a [&'a str],
+    /// Remove items whose content has been deleted or is otherwise inaccessible.
+    pub remove_inaccessible_items: bool,
+    pub scope: SyncScope<'a>,
+    pub audit: &'a AuditLog,
+    /// The name of the stage doing the syncing, for the audit log.
+    pub stage: &'a str,
 }
 
-/// Sync issue IDs given with items in the `field_status_value_name` field in the project.
+/// Where an issue or PR should be on a project, and why.
+#[derive(Debug, Copy, Clone)]
+pub struct Placement<'a> {
+    /// The name of the status column it should be in.
+    pub status: &'a str,
+    /// Why it should be there, for the audit log (eg the name of a rule).
+    pub reason: &'a str,
+    /// Any other fields to set on it, as field IDs and values. Fields with no value are cleared.
+    pub fields: &'a [(String, Option<FieldValue>)],
+}
+
+/// Which project items a sync is allowed to add or remove.
+#[derive(Debug, Copy, Clone)]
+pub enum SyncScope<'a> {
+    /// Any item; this is what a normal full sync uses. This is the only scope which
+    /// includes items whose content is inaccessible.
+    All,
+    /// Only items whose content (ie issue or PR) has one of these IDs. Used to re-sync
+    /// individual things in response to webhook events. Since we only care about these,
+    /// we only look for things updated since the least recently updated of them was.
+    Content { ids: &'a [String], updated_since: time::OffsetDateTime },
+    /// Only items whose content has been updated since the given time. Used for
+    /// incremental syncs, where we only look for things updated since the last run.
+    /// Search results lag a little behind updates, so items updated after `settled_before`
+    /// may be missing from them and aren't removed; a later sync will deal with them.
+    UpdatedSince { since: time::OffsetDateTime, settled_before: time::OffsetDateTime },
+}
+
+impl <'a> SyncScope<'a> {
+    /// Can the content (ie issue or PR) with the given ID be added to the project? When syncing
+    /// incrementally, the IDs we're given are already only those which have been updated.
+    pub fn includes_content_id(&self, content_id: &str) -> bool {
+        match self {
+            SyncScope::All | SyncScope::UpdatedSince { .. } => true,
+            SyncScope::Content { ids, .. } => ids.iter().any(|id| id == content_id),
+        }
+    }
+
+    /// Can the given project item be removed from the project?
+    pub fn includes_item(&self, item: &ProjectItem) -> bool {
+        match self {
+            SyncScope::All => true,
+            SyncScope::Content { ids, .. } => ids.iter().any(|id| item.content_id.as_ref() == Some(id)),
+            SyncScope::UpdatedSince { since, settled_before } => item.content_updated_at.is_some_and(|at| at >= *since && at < *settled_before),
+        }
+    }
+
+    /// If we only care about things updated since some time, this returns it.
+    pub fn updated_since(&self) -> Option<time::OffsetDateTime> {
+        match self {
+            SyncScope::UpdatedSince { since, .. } => Some(*since),
+            SyncScope::Content { updated_since, .. } => Some(*updated_since),
+            SyncScope::All => None,
+        }
+    }
+}
+
+/// Sync the given placements with the items in the managed status columns of the project.
 pub async fn sync_issues_to_project(opts: SyncIssuesToProjectOpts<'_>) -> Result<(), anyhow::Error> {
     let SyncIssuesToProjectOpts {
         api,
-        org,
-        field_status_value_name,
         project_details,
-        issue_ids,
+        items: all_items,
+        managed_statuses,
+        placements,
+        archived_statuses,
+        remove_inaccessible_items,
+        scope,
+        audit,
+        stage,
     } = opts;
 
-    // the id of the status column we want to get items for:
-    let status_field_value_id = project_details.status.options
-        .iter()
-        .find(|o| o.name.trim().to_ascii_lowercase().starts_with(&field_status_value_name.to_ascii_lowercase()))
-        .map(|o| &*o.id)
-        .ok_or(anyhow::anyhow!("Could not find the '{field_status_value_name}' status in the local project board"))?;
+    // The ids of the status columns we manage:
+    let mut status_ids: HashMap<&str, &str> = HashMap::new();
+    for &status_name in managed_statuses {
+        let status_id = find_status_id(project_details, status_name)?;
+        status_ids.insert(status_name, status_id);
+    }
+    let is_managed = |status_id: &str| status_ids.values().any(|id| *id == status_id);
+    let is_archived_on_removal = |status_id: &str| archived_statuses.iter().any(|name| status_ids.get(name) == Some(&status_id));
 
-    // Items in that column:
-    let items: Vec<_> = query::project_items::run(api, org, project_details.number)
-        .await?
-        .into_iter()
-        .filter(|item| item.status_field_value_id.as_deref() == Some(status_field_value_id))
-        .collect();
+    // Do a naive diff to work out which issues to add, which items to move between
+    // columns, and which items to remove:
+    let mut to_add = Vec::new();
+    let mut to_move = Vec::new();
+    let mut to_remove = Vec::new();
+    let mut to_archive = Vec::new();
+    let mut to_unarchive = Vec::new();
+    let mut to_update_fields = Vec::new();
+    for item in all_items {
+        // We can't route things we can't see, but we can tidy them away if asked to.
+        let Some(content_id) = &item.content_id else {
+            warn!("🛑 {} on project board (its content was deleted or we can't access it)", item.describe());
+            if remove_inaccessible_items && scope.includes_item(item) {
+                to_remove.push(item);
+            }
+            continue
+        };
+        // Archived items are left alone unless they belong on the board again.
+        if item.is_archived {
+            match placements.get(content_id) {
+                Some(placement) if scope.includes_content_id(content_id) => to_unarchive.push((item, placement)),
+                _ => continue
+            }
+        }
+        let current_status_id = item.status_field_value_id.as_deref();
+        let is_in_managed_column = current_status_id.is_some_and(is_managed);
+        match placements.get(content_id) {
+            Some(placement) => {
+                if !scope.includes_content_id(content_id) {
+                    continue
+                }
+                if current_status_id != Some(status_ids[placement.status]) {
+                    to_move.push((item, placement));
+                }
+                for (field_id, value) in placement.fields {
+                    let current_value = item.fields.values().find(|f| &f.field_id == field_id).map(|f| &f.value);
+                    if current_value != value.as_ref() {
+                        to_update_fields.push((item, placement, field_id, current_value, value));
+                    }
+                }
+            },
+            None => {
+                if is_in_managed_column && scope.includes_item(item) {
+                    match current_status_id.is_some_and(is_archived_on_removal) {
+                        true => to_archive.push(item),
+                        false => to_remove.push(item),
+                    }
+                }
+            }
+        }
+    }
+    for (content_id, placement) in placements {
+        let is_on_board = all_items.iter().any(|item| item.content_id.as_ref() == Some(content_id));
+        if !is_on_board && scope.includes_content_id(content_id) {
+            to_add.push((content_id, placement));
+        }
+    }
 
-    // Do a naive diff to work out which issues to add and which items to remove:
-    let issue_ids_to_add: Vec<_> = issue_ids
-        .iter()
-        .filter(|issue_id| !items.iter().any(|item| &item.content_id == *issue_id))
-        .collect();
-    let item_ids_to_remove: Vec<_> = items
-        .iter()
-        .filter(|item| !issue_ids.iter().any(|issue| issue == &item.content_id))
-        .map(|item| &*item.item_id)
-        .collect();
-
-    if !issue_ids_to_add.is_empty() {
-        info!("✅ creating {} items in `{}` on project board", issue_ids_to_add.len(), field_status_value_name);
-        for issue_id in issue_ids_to_add {
-            let item_id = mutation::add_item_to_project::run(
-                api,
-                issue_id,
-                &project_details.id
-            ).await?;
-            mutation::update_item_field_in_project::run(
-                api,
+    let mutations = audit.mutations(api, stage);
+
+    if !to_add.is_empty() {
+        info!("✅ creating {} items on project board", to_add.len());
+        for (content_id, placement) in to_add {
+            let item_id = mutations.add_item(placement.reason, &project_details.id, content_id).await?;
+            mutations.set_item_option(
+                placement.reason,
                 &project_details.id,
                 &item_id,
                 &project_details.status.id,
-                status_field_value_id
+                None,
+                status_ids[placement.status]
+            ).await?;
+            for (field_id, value) in placement.fields {
+                if let Some(value) = value {
+                    mutations.set_item_field(placement.reason, &project_details.id, &item_id, field_id, None, value.clone()).await?;
+                }
+            }
+        }
+    }
+
+    if !to_unarchive.is_empty() {
+        info!("📤 unarchiving {} items on project board", to_unarchive.len());
+        for (item, placement) in to_unarchive {
+            mutations.unarchive_item(placement.reason, &project_details.id, &item.item_id).await?;
+        }
+    }
+
+    if !to_move.is_empty() {
+        // Items that are already on the board (in some other column) are just moved.
+        info!("➡️  moving {} items on project board", to_move.len());
+        for (item, placement) in to_move {
+            info!("➡️  moving {} to `{}`", item.describe(), placement.status);
+            mutations.set_item_option(
+                placement.reason,
+                &project_details.id,
+                &item.item_id,
+                &project_details.status.id,
+                item.status_field_value_id.as_deref(),
+                status_ids[placement.status]
             ).await?;
         }
     }
 
-    if !item_ids_to_remove.is_empty() {
-        info!("❌ removing {} items in `{}` on project board", item_ids_to_remove.len(),field_status_value_name);
-        for item_id in item_ids_to_remove {
-            mutation::remove_item_from_project::run(
-                api,
+    if !to_update_fields.is_empty() {
+        info!("☑️  updating {} field values on project board", to_update_fields.len());
+        for (item, placement, field_id, current_value, value) in to_update_fields {
+            match value {
+                Some(value) => {
+                    mutations.set_item_field(
+                        placement.reason,
+                        &project_details.id,
+                        &item.item_id,
+                        field_id,
+                        current_value.cloned(),
+                        value.clone()
+                    ).await?;
+                },
+                None => {
+                    mutations.clear_item_field(
+                        placement.reason,
+                        &project_details.id,
+                        &item.item_id,
+                        field_id,
+                        current_value.cloned()
+                    ).await?;
+                }
+            }
+        }
+    }
+
+    if !to_remove.is_empty() {
+        info!("❌ removing {} items on project board", to_remove.len());
+        for item in to_remove {
+            info!("❌ removing {}", item.describe());
+            let reason = match item.content_id {
+                Some(_) => "no longer matches any rule",
+                None => "content is inaccessible"
+            };
+            mutations.remove_item(
+                reason,
                 &project_details.id,
-                item_id
+                &item.item_id,
+                item.content_id.as_deref(),
+                previous_field_values(item)
             ).await?;
         }
     }
 
+    if !to_archive.is_empty() {
+        info!("📥 archiving {} items on project board", to_archive.len());
+        for item in to_archive {
+            info!("📥 archiving {}", item.describe());
+            mutations.archive_item("no longer matches any rule", &project_details.id, &item.item_id).await?;
+        }
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Every field value set on a project item, so that we can put them back if need be.
+pub fn previous_field_values(item: &ProjectItem) -> Vec<PreviousFieldValue> {
+    item.fields
+        .values()
+        .map(|f| PreviousFieldValue { field_id: f.field_id.clone(), value: f.value.clone() })
+        .collect()
+}
+
+/// Find the ID of the first status in the project whose name starts with the one given.
+pub fn find_status_id<'a>(project_details: &'a ToolsProject, status_name: &str) -> Result<&'a str, anyhow::Error> {
+    project_details.status.options
+        .iter()
+        .find(|o| o.name.trim().to_ascii_lowercase().starts_with(&status_name.to_ascii_lowercase()))
+        .map(|o| &*o.id)
+        .ok_or(anyhow::anyhow!("Could not find the '{status_name}' status in project {}", project_details.number))
+}
+
+/// Repos can be given as `owner/name`, or as just `name` for repos in the given default org.
+/// This returns the `owner/name` form either way.
+pub fn full_repo_name(default_org: &str, repo: &str) -> String {
+    if repo.contains('/') {
+        repo.to_owned()
+    } else {
+        format!("{default_org}/{repo}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::query::project_items::ProjectItemContent;
+
+    fn at(minutes: i64) -> time::OffsetDateTime {
+        time::OffsetDateTime::from_unix_timestamp(1_700_000_000 + minutes * 60).expect("valid timestamp")
+    }
+
+    fn item(content_id: Option<&str>, updated_at: Option<time::OffsetDateTime>) -> ProjectItem {
+        ProjectItem {
+            item_id: "item".to_owned(),
+            content_id: content_id.map(|id| id.to_owned()),
+            content_updated_at: updated_at,
+            status_field_value_id: None,
+            is_archived: false,
+            content: ProjectItemContent::Inaccessible,
+            fields: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn updated_since_only_includes_settled_updates() {
+        let scope = SyncScope::UpdatedSince { since: at(0), settled_before: at(10) };
+        assert!(!scope.includes_item(&item(Some("a"), Some(at(-1)))));
+        assert!(scope.includes_item(&item(Some("a"), Some(at(0)))));
+        assert!(scope.includes_item(&item(Some("a"), Some(at(9)))));
+        // Search results may not have caught up with these yet.
+        assert!(!scope.includes_item(&item(Some("a"), Some(at(10)))));
+        assert!(!scope.includes_item(&item(Some("a"), Some(at(11)))));
+        // Drafts and inaccessible items are never updated as far as we know.
+        assert!(!scope.includes_item(&item(Some("a"), None)));
+        assert!(!scope.includes_item(&item(None, None)));
+        // Anything found by the searches can be added.
+        assert!(scope.includes_content_id("a"));
+        assert_eq!(scope.updated_since(), Some(at(0)));
+    }
+
+    #[test]
+    fn content_only_includes_the_given_ids() {
+        let ids = ["a".to_owned(), "b".to_owned()];
+        let scope = SyncScope::Content { ids: &ids, updated_since: at(0) };
+        assert!(scope.includes_item(&item(Some("b"), Some(at(-100)))));
+        assert!(!scope.includes_item(&item(Some("c"), Some(at(5)))));
+        assert!(!scope.includes_item(&item(None, None)));
+        assert!(scope.includes_content_id("a"));
+        assert!(!scope.includes_content_id("c"));
+        assert_eq!(scope.updated_since(), Some(at(0)));
+    }
+
+    #[test]
+    fn all_includes_everything() {
+        let scope = SyncScope::All;
+        assert!(scope.includes_item(&item(None, None)));
+        assert!(scope.includes_item(&item(Some("a"), Some(at(-100)))));
+        assert!(scope.includes_content_id("a"));
+        assert_eq!(scope.updated_since(), None);
+    }
+}